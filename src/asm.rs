@@ -0,0 +1,506 @@
+//! Assembler for `.bpasm` ByteByteJump sources
+//!
+//! Every line holds an optional `label:`, an optional mnemonic with its
+//! comma separated operands and an optional `;` comment. Mnemonics mirror
+//! the `RomBuilder` opcodes:
+//!
+//! ```text
+//! ; header registers
+//!         org 0
+//!         init_regs 0, start, 0x010000, audio
+//!
+//!         org 0x000100
+//!         install_id_table
+//!         install_inc_table
+//!
+//!         section program, 0x000300
+//! start:  sync
+//!         inc 0x000005
+//!         jmp start
+//!
+//!         section audio, 0x00FF00
+//! audio:  db 0, 0, 0, 0
+//!         incbin "samples.raw"
+//! ```
+//!
+//! Operands are numbers (decimal, `0x` hex, `0b` binary), labels or sums
+//! and differences of them (`table+3`). Labels can be used before being
+//! defined, the source is assembled twice so that every address is known.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::error::BytePusherError;
+use crate::rom_builder::RomBuilder;
+
+/// Assemble a source into a ROM, `incbin` paths are relative to the working directory
+pub fn assemble(source: &str) -> Result<RomBuilder, BytePusherError> {
+    Assembler::new(source, Path::new(""))?.run()
+}
+
+/// Assemble a source file into a ROM, `incbin` paths are relative to the file
+pub fn assemble_file(filename: &str) -> Result<RomBuilder, BytePusherError> {
+    let source = std::fs::read_to_string(filename)?;
+    let base_dir = Path::new(filename).parent().unwrap_or(Path::new(""));
+    Assembler::new(&source, base_dir)?.run()
+}
+
+#[derive(Debug)]
+struct Line {
+    number: usize,
+    label: Option<String>,
+    mnemonic: Option<String>,
+    operands: Vec<String>,
+}
+
+struct Assembler {
+    lines: Vec<Line>,
+    base_dir: PathBuf,
+    /// Label addresses found by the previous pass
+    symbols: HashMap<String, usize>,
+    /// Whether undefined labels are errors (final pass) or placeholders
+    final_pass: bool,
+}
+
+fn error(line: usize, message: impl Into<String>) -> BytePusherError {
+    BytePusherError::ParseError {
+        line,
+        message: message.into(),
+    }
+}
+
+fn is_identifier(text: &str) -> bool {
+    let mut chars = text.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == '.')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+}
+
+fn parse_number(text: &str) -> Option<usize> {
+    if let Some(hex) = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        usize::from_str_radix(hex, 16).ok()
+    } else if let Some(bin) = text.strip_prefix("0b").or_else(|| text.strip_prefix("0B")) {
+        usize::from_str_radix(bin, 2).ok()
+    } else {
+        text.parse().ok()
+    }
+}
+
+/// Split operands on commas that are not inside a quoted string
+fn split_operands(text: &str) -> Vec<String> {
+    let mut operands = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    for c in text.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                current.push(c);
+            }
+            ',' if !quoted => operands.push(std::mem::take(&mut current).trim().to_string()),
+            c => current.push(c),
+        }
+    }
+    if !current.trim().is_empty() || !operands.is_empty() {
+        operands.push(current.trim().to_string());
+    }
+    operands
+}
+
+/// Remove a `;` comment that is not inside a quoted string
+fn strip_comment(text: &str) -> &str {
+    let mut quoted = false;
+    for (i, c) in text.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ';' if !quoted => return &text[..i],
+            _ => {}
+        }
+    }
+    text
+}
+
+impl Assembler {
+    fn new(source: &str, base_dir: &Path) -> Result<Self, BytePusherError> {
+        let mut lines = Vec::new();
+
+        for (index, text) in source.lines().enumerate() {
+            let number = index + 1;
+            let mut text = strip_comment(text).trim();
+
+            let mut label = None;
+            if let Some((name, rest)) = text.split_once(':')
+                && is_identifier(name.trim())
+            {
+                label = Some(name.trim().to_string());
+                text = rest.trim();
+            }
+
+            let (mnemonic, operands) = match text.split_once(char::is_whitespace) {
+                Some((mnemonic, rest)) => (mnemonic, split_operands(rest)),
+                None => (text, Vec::new()),
+            };
+            if operands.iter().any(|operand| operand.is_empty()) {
+                return Err(error(number, "empty operand"));
+            }
+
+            lines.push(Line {
+                number,
+                label,
+                mnemonic: (!mnemonic.is_empty()).then(|| mnemonic.to_lowercase()),
+                operands,
+            });
+        }
+
+        Ok(Self {
+            lines,
+            base_dir: base_dir.to_path_buf(),
+            symbols: HashMap::new(),
+            final_pass: false,
+        })
+    }
+
+    fn run(mut self) -> Result<RomBuilder, BytePusherError> {
+        // Instruction sizes do not depend on operand values, so the first pass
+        // finds every label address and the second one emits the final ROM
+        let (_, symbols) = self.pass()?;
+        self.symbols = symbols;
+        self.final_pass = true;
+        let (rom, symbols) = self.pass()?;
+
+        // A label moves only if its address depends on a forward reference (e.g. `org later`)
+        if let Some(line) = self.lines.iter().find(|line| {
+            line.label
+                .as_ref()
+                .is_some_and(|name| symbols.get(name) != self.symbols.get(name))
+        }) {
+            return Err(error(
+                line.number,
+                "label address depends on a forward reference",
+            ));
+        }
+
+        rom.finalize()?;
+        Ok(rom)
+    }
+
+    fn pass(&self) -> Result<(RomBuilder, HashMap<String, usize>), BytePusherError> {
+        let mut rom = RomBuilder::new();
+        let mut symbols = HashMap::new();
+
+        for line in &self.lines {
+            if let Some(name) = &line.label {
+                if symbols.contains_key(name) {
+                    return Err(error(
+                        line.number,
+                        format!("label '{}' already defined", name),
+                    ));
+                }
+                symbols.insert(name.clone(), rom.get_current_addr());
+                let label = rom.new_label(name);
+                rom.label(label);
+            }
+
+            if let Some(mnemonic) = &line.mnemonic {
+                self.emit(&mut rom, line, mnemonic).map_err(|e| match e {
+                    BytePusherError::ParseError { .. } => e,
+                    e => error(line.number, e.to_string()),
+                })?;
+            }
+        }
+
+        Ok((rom, symbols))
+    }
+
+    fn eval(&self, line: &Line, operand: &str) -> Result<usize, BytePusherError> {
+        let mut total: i64 = 0;
+        let mut sign = 1;
+        let mut term = String::new();
+
+        let mut apply = |term: &str, sign: i64| -> Result<(), BytePusherError> {
+            let term = term.trim();
+            let value = if let Some(value) = parse_number(term) {
+                value
+            } else if is_identifier(term) {
+                match self.symbols.get(term) {
+                    Some(&value) => value,
+                    None if self.final_pass => {
+                        return Err(error(line.number, format!("undefined label '{}'", term)));
+                    }
+                    None => 0,
+                }
+            } else {
+                return Err(error(line.number, format!("invalid operand '{}'", operand)));
+            };
+            total += sign * value as i64;
+            Ok(())
+        };
+
+        for c in operand.chars() {
+            match c {
+                '+' | '-' => {
+                    apply(&term, sign)?;
+                    term.clear();
+                    sign = if c == '+' { 1 } else { -1 };
+                }
+                c => term.push(c),
+            }
+        }
+        apply(&term, sign)?;
+
+        if !(0..=0xFFFFFF).contains(&total) {
+            return Err(error(
+                line.number,
+                format!("value of '{}' out of range", operand),
+            ));
+        }
+        Ok(total as usize)
+    }
+
+    fn byte(&self, line: &Line, operand: &str) -> Result<u8, BytePusherError> {
+        let value = self.eval(line, operand)?;
+        u8::try_from(value)
+            .map_err(|_| error(line.number, format!("'{}' does not fit in a byte", operand)))
+    }
+
+    fn operands<const N: usize>(&self, line: &Line) -> Result<[usize; N], BytePusherError> {
+        if line.operands.len() != N {
+            return Err(error(
+                line.number,
+                format!("expected {} operands, found {}", N, line.operands.len()),
+            ));
+        }
+        let mut values = [0; N];
+        for (value, operand) in values.iter_mut().zip(&line.operands) {
+            *value = self.eval(line, operand)?;
+        }
+        Ok(values)
+    }
+
+    fn emit(
+        &self,
+        rom: &mut RomBuilder,
+        line: &Line,
+        mnemonic: &str,
+    ) -> Result<(), BytePusherError> {
+        match mnemonic {
+            "org" => {
+                let [addr] = self.operands(line)?;
+                rom.org(addr);
+            }
+            "section" => {
+                let (name, addr) = match line.operands.as_slice() {
+                    [name, addr] if !name.contains(char::is_whitespace) => (name, addr),
+                    _ => return Err(error(line.number, "expected 'section <name>, <address>'")),
+                };
+                rom.section(name, self.eval(line, addr)?);
+            }
+            "init_regs" => {
+                let [keys, program, screen, audio] = self.operands(line)?;
+                rom.check_space(8)?;
+                rom.init_regs(keys as u16, program, screen, audio);
+            }
+            "install_id_table" => {
+                self.operands::<0>(line)?;
+                rom.try_install_id_table()?;
+            }
+            "install_inc_table" => {
+                self.operands::<0>(line)?;
+                rom.try_install_inc_table()?;
+            }
+            "bbj" => {
+                let [source, target, jump] = self.operands(line)?;
+                rom.try_bbj(source, target, jump)?;
+            }
+            "nop" | "wait" => {
+                self.operands::<0>(line)?;
+                if mnemonic == "nop" {
+                    rom.try_nop()?;
+                } else {
+                    rom.try_wait()?;
+                }
+            }
+            "sync" => {
+                self.operands::<0>(line)?;
+                rom.try_sync()?;
+            }
+            "jmp" => {
+                let [addr] = self.operands(line)?;
+                rom.try_jmp(addr)?;
+            }
+            "cpy" => {
+                let [source, target] = self.operands(line)?;
+                rom.try_cpy(source, target)?;
+            }
+            "cpyi" => {
+                if line.operands.len() != 2 {
+                    return Err(error(line.number, "expected 'cpyi <value>, <target>'"));
+                }
+                let value = self.byte(line, &line.operands[0])?;
+                let target = self.eval(line, &line.operands[1])?;
+                rom.try_cpyi(value, target)?;
+            }
+            "cpyi_addr" => {
+                let [value, target] = self.operands(line)?;
+                rom.try_cpyi_addr(value, target)?;
+            }
+            "inc" => {
+                let [addr] = self.operands(line)?;
+                rom.try_inc(addr)?;
+            }
+            "dec" => {
+                let [addr] = self.operands(line)?;
+                rom.try_dec(addr)?;
+            }
+            "inc16" | "inc24" | "dec16" => {
+                let [addr] = self.operands(line)?;
+                match mnemonic {
+                    "inc16" => rom.try_inc16(addr)?,
+                    "inc24" => rom.try_inc24(addr)?,
+                    _ => rom.try_dec16(addr)?,
+                };
+            }
+            "add16" => {
+                let [a, b] = self.operands(line)?;
+                rom.try_add16(a, b)?;
+            }
+            "add_imm" => {
+                if line.operands.len() != 2 {
+                    return Err(error(line.number, "expected 'add_imm <target>, <value>'"));
+                }
+                let addr = self.eval(line, &line.operands[0])?;
+                let value = self.byte(line, &line.operands[1])?;
+                rom.try_add_imm(addr, value)?;
+            }
+            "add" | "sub" | "and" | "or" | "xor" => {
+                let [a, b] = self.operands(line)?;
+                match mnemonic {
+                    "add" => rom.try_add(a, b)?,
+                    "sub" => rom.try_sub(a, b)?,
+                    "and" => rom.try_and(a, b)?,
+                    "or" => rom.try_or(a, b)?,
+                    _ => rom.try_xor(a, b)?,
+                };
+            }
+            "and_imm" | "or_imm" | "xor_imm" => {
+                if line.operands.len() != 2 {
+                    return Err(error(
+                        line.number,
+                        format!("expected '{} <target>, <value>'", mnemonic),
+                    ));
+                }
+                let addr = self.eval(line, &line.operands[0])?;
+                let value = self.byte(line, &line.operands[1])?;
+                match mnemonic {
+                    "and_imm" => rom.try_and_imm(addr, value)?,
+                    "or_imm" => rom.try_or_imm(addr, value)?,
+                    _ => rom.try_xor_imm(addr, value)?,
+                };
+            }
+            "branch_if_zero" => {
+                let [addr, target] = self.operands(line)?;
+                rom.try_branch_if_zero(addr, target)?;
+            }
+            "branch_if_eq" | "branch_if_lt" => {
+                let [a, b, target] = self.operands(line)?;
+                if mnemonic == "branch_if_eq" {
+                    rom.try_branch_if_eq(a, b, target)?;
+                } else {
+                    rom.try_branch_if_lt(a, b, target)?;
+                }
+            }
+            "not" | "shl" | "shr" => {
+                let [addr] = self.operands(line)?;
+                match mnemonic {
+                    "not" => rom.try_not(addr)?,
+                    "shl" => rom.try_shl(addr)?,
+                    _ => rom.try_shr(addr)?,
+                };
+            }
+            "db" => {
+                let bytes = line
+                    .operands
+                    .iter()
+                    .map(|operand| self.byte(line, operand))
+                    .collect::<Result<Vec<u8>, _>>()?;
+                rom.check_space(bytes.len())?;
+                rom.db_arr(&bytes);
+            }
+            "incbin" => {
+                let path = match line.operands.as_slice() {
+                    [path] if path.len() >= 2 && path.starts_with('"') && path.ends_with('"') => {
+                        &path[1..path.len() - 1]
+                    }
+                    _ => return Err(error(line.number, "expected 'incbin \"<file>\"'")),
+                };
+                let data = std::fs::read(self.base_dir.join(path))?;
+                rom.check_space(data.len())?;
+                rom.db_arr(&data);
+            }
+            _ => {
+                return Err(error(
+                    line.number,
+                    format!("unknown mnemonic '{}'", mnemonic),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vm::Machine;
+
+    #[test]
+    fn test_assemble_and_run() {
+        let rom = assemble(
+            "; cycle the screen between two pages\n\
+                     org 0\n\
+                     init_regs 0, start, 0x010000, audio\n\
+                     org 0x000100\n\
+                     install_id_table\n\
+                     install_inc_table\n\
+                     section program, 0x000300\n\
+             start:  sync\n\
+                     inc 0x000005\n\
+                     sync\n\
+                     cpyi 0x01, 0x000005 ; back to first page\n\
+                     jmp start\n\
+             audio:  db 1, 2, 3, 0x04\n",
+        )
+        .unwrap();
+
+        assert_eq!(rom[0x000002..0x000005], [0x00, 0x03, 0x00]);
+        assert_eq!(rom[0x000006..0x000008], [0x00, 0x03]);
+
+        let mut machine = Machine::from_builder(&rom).unwrap();
+        let mut pages = Vec::new();
+        for _ in 0..4 {
+            machine.run_frame();
+            pages.push(machine.screen_addr() >> 16);
+        }
+        assert_eq!(pages, [1, 2, 1, 2]);
+    }
+
+    #[test]
+    fn test_assemble_errors_report_line() {
+        for (source, line) in [
+            ("nop\nfoo 1\n", 2),
+            ("org 0x100\njmp nowhere\n", 2),
+            ("a: nop\na: nop\n", 2),
+            ("org 0x10\ninstall_inc_table\n", 2),
+            ("db 256\n", 1),
+            ("org later+9\nlater: nop\n", 2),
+        ] {
+            match assemble(source) {
+                Err(BytePusherError::ParseError { line: found, .. }) => {
+                    assert_eq!(found, line, "{}", source)
+                }
+                other => panic!("unexpected result for {:?}: {:?}", source, other.err()),
+            }
+        }
+    }
+}
@@ -0,0 +1,86 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use crate::error::BytePusherError;
+use crate::vm::{AUDIO_SAMPLES_PER_FRAME, Machine};
+
+/// BytePusher plays 256 samples per frame at 60 frames per second
+pub const SAMPLE_RATE: u32 = 15360;
+
+/// Run the machine for the given number of frames,
+/// returning the signed 8-bit samples played at the end of each frame
+pub fn capture_audio(machine: &mut Machine, frames: usize) -> Vec<u8> {
+    let mut samples = Vec::with_capacity(frames * AUDIO_SAMPLES_PER_FRAME);
+    for _ in 0..frames {
+        machine.run_frame();
+        samples.extend_from_slice(machine.audio());
+    }
+    samples
+}
+
+/// Write signed 8-bit samples as a 15360 Hz mono WAV stream
+/// WAV 8-bit PCM is unsigned, so every sample is shifted by 128
+pub fn write_wav<W: Write>(samples: &[u8], writer: &mut W) -> std::io::Result<()> {
+    let data_len = samples.len() as u32;
+
+    writer.write_all(b"RIFF")?;
+    // The RIFF size counts the pad byte of an odd data chunk
+    writer.write_all(&(36 + data_len + (data_len & 1)).to_le_bytes())?;
+    writer.write_all(b"WAVE")?;
+
+    writer.write_all(b"fmt ")?;
+    writer.write_all(&16u32.to_le_bytes())?; // chunk size
+    writer.write_all(&1u16.to_le_bytes())?; // PCM
+    writer.write_all(&1u16.to_le_bytes())?; // mono
+    writer.write_all(&SAMPLE_RATE.to_le_bytes())?;
+    writer.write_all(&SAMPLE_RATE.to_le_bytes())?; // byte rate
+    writer.write_all(&1u16.to_le_bytes())?; // block align
+    writer.write_all(&8u16.to_le_bytes())?; // bits per sample
+
+    writer.write_all(b"data")?;
+    writer.write_all(&data_len.to_le_bytes())?;
+    let unsigned: Vec<u8> = samples.iter().map(|&s| s ^ 0x80).collect();
+    writer.write_all(&unsigned)?;
+    if data_len % 2 == 1 {
+        writer.write_all(&[0])?;
+    }
+    Ok(())
+}
+
+/// Save signed 8-bit samples as a WAV file
+pub fn save_wav(samples: &[u8], path: &str) -> Result<(), BytePusherError> {
+    let mut file = BufWriter::new(File::create(path)?);
+    write_wav(samples, &mut file)?;
+    file.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wav_header_and_samples() {
+        let mut wav = Vec::new();
+        write_wav(&[0x00, 0x7F, 0x80, 0xFF], &mut wav).unwrap();
+
+        assert_eq!(wav.len(), 44 + 4);
+        assert_eq!(&wav[0..4], b"RIFF");
+        assert_eq!(&wav[8..12], b"WAVE");
+        assert_eq!(u32::from_le_bytes(wav[24..28].try_into().unwrap()), 15360);
+        assert_eq!(u32::from_le_bytes(wav[40..44].try_into().unwrap()), 4);
+        assert_eq!(&wav[44..], [0x80, 0xFF, 0x00, 0x7F]);
+        assert_eq!(u32::from_le_bytes(wav[4..8].try_into().unwrap()), 40);
+    }
+
+    #[test]
+    fn test_wav_odd_data_is_padded() {
+        let mut wav = Vec::new();
+        write_wav(&[0x00, 0x7F, 0x80], &mut wav).unwrap();
+
+        assert_eq!(wav.len(), 44 + 4);
+        assert_eq!(u32::from_le_bytes(wav[4..8].try_into().unwrap()), 40);
+        assert_eq!(u32::from_le_bytes(wav[40..44].try_into().unwrap()), 3);
+        assert_eq!(&wav[44..], [0x80, 0xFF, 0x00, 0x00]);
+    }
+}
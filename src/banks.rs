@@ -0,0 +1,279 @@
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::ops::Range;
+use std::sync::OnceLock;
+
+use crate::error::BytePusherError;
+use crate::rom_builder::MEMORY_SIZE;
+
+/// Size of a bank of the sparse store, the same as a screen page
+pub const BANK_SIZE: usize = 65536;
+
+/// Content of the banks that have never been written
+static ZERO_MEMORY: [u8; MEMORY_SIZE] = [0; MEMORY_SIZE];
+
+/// Sparse 16 MiB memory, 64 KiB banks are allocated on their first write
+/// Consecutive allocated banks are stored contiguously, and a mutable slice spanning
+/// several banks allocates the banks it touches, so that it can be borrowed
+#[derive(Debug, Clone, Default)]
+pub struct BankStore {
+    /// Contiguous runs of allocated banks, keyed by their first bank
+    segments: BTreeMap<usize, Vec<u8>>,
+    /// Contiguous copy of the whole memory, built when a slice spans written and
+    /// unwritten banks, and dropped on the next write
+    flat: OnceLock<Vec<u8>>,
+}
+
+impl BankStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// First bank and content of the segment holding the given bank
+    fn segment_of(&self, bank: usize) -> Option<(usize, &[u8])> {
+        let (&first, data) = self.segments.range(..=bank).next_back()?;
+        (bank < first + data.len() / BANK_SIZE).then_some((first, &data[..]))
+    }
+
+    fn bank(&self, bank: usize) -> &[u8] {
+        match self.segment_of(bank) {
+            Some((first, data)) => &data[(bank - first) * BANK_SIZE..][..BANK_SIZE],
+            None => &ZERO_MEMORY[..BANK_SIZE],
+        }
+    }
+
+    /// Panic like `try_write_u8()` when `range` goes past the 16 MiB address space
+    pub(crate) fn check_bounds(range: &Range<usize>) {
+        if range.end > MEMORY_SIZE {
+            let addr = range.start.max(MEMORY_SIZE);
+            panic!("{}", BytePusherError::AddressOutOfRange(addr));
+        }
+    }
+
+    fn bank_mut(&mut self, bank: usize) -> &mut [u8] {
+        self.banks_mut(bank..bank + 1)
+    }
+
+    /// Contiguous content of the given banks, allocating them and merging them with
+    /// the segments they touch or follow, so that consecutive banks are always contiguous
+    fn banks_mut(&mut self, banks: Range<usize>) -> &mut [u8] {
+        self.flat.take();
+        let touched: Vec<(usize, usize)> = self
+            .segments
+            .range(..=banks.end)
+            .map(|(&first, data)| (first, first + data.len() / BANK_SIZE))
+            .filter(|&(_, end)| end >= banks.start)
+            .collect();
+        let first = touched
+            .first()
+            .map_or(banks.start, |&(f, _)| f.min(banks.start));
+        let end = touched.last().map_or(banks.end, |&(_, e)| e.max(banks.end));
+
+        if touched.len() != 1 || touched[0] != (first, end) {
+            // Growing the segment at `first` in place makes sequential writes cheap
+            let mut data = match touched.first() {
+                Some(&(segment, _)) if segment == first => self.segments.remove(&first).unwrap(),
+                _ => Vec::new(),
+            };
+            data.resize((end - first) * BANK_SIZE, 0);
+            for &(segment, _) in touched.iter().filter(|&&(segment, _)| segment != first) {
+                let old = self.segments.remove(&segment).unwrap();
+                data[(segment - first) * BANK_SIZE..][..old.len()].copy_from_slice(&old);
+            }
+            self.segments.insert(first, data);
+        }
+        let data = self.segments.get_mut(&first).unwrap();
+        &mut data[(banks.start - first) * BANK_SIZE..(banks.end - first) * BANK_SIZE]
+    }
+
+    /// Banks touched by a non-empty range
+    fn banks_of(range: &Range<usize>) -> Range<usize> {
+        range.start / BANK_SIZE..range.end.div_ceil(BANK_SIZE)
+    }
+
+    /// Number of banks allocated so far
+    pub fn allocated_banks(&self) -> usize {
+        self.segments
+            .values()
+            .map(|data| data.len() / BANK_SIZE)
+            .sum()
+    }
+
+    /// Copy of the bytes in `range`, which may span several banks
+    pub fn read(&self, range: Range<usize>) -> Vec<u8> {
+        let mut data = Vec::with_capacity(range.len());
+        let mut addr = range.start;
+        while addr < range.end {
+            let (bank, offset) = (addr / BANK_SIZE, addr % BANK_SIZE);
+            let len = (BANK_SIZE - offset).min(range.end - addr);
+            data.extend_from_slice(&self.bank(bank)[offset..offset + len]);
+            addr += len;
+        }
+        data
+    }
+
+    /// Copy `data` starting at `addr`, zeros written to unallocated banks are skipped
+    pub fn write(&mut self, addr: usize, data: &[u8]) {
+        let (mut addr, mut data) = (addr, data);
+        while !data.is_empty() {
+            let (bank, offset) = (addr / BANK_SIZE, addr % BANK_SIZE);
+            let len = (BANK_SIZE - offset).min(data.len());
+            let (chunk, rest) = data.split_at(len);
+            if self.segment_of(bank).is_some() || chunk.iter().any(|&b| b != 0) {
+                self.bank_mut(bank)[offset..offset + len].copy_from_slice(chunk);
+            }
+            addr += len;
+            data = rest;
+        }
+    }
+
+    /// Address following the last non-zero byte
+    pub fn used_len(&self) -> usize {
+        for (first, data) in self.segments.iter().rev() {
+            if let Some(last) = data.iter().rposition(|&b| b != 0) {
+                return first * BANK_SIZE + last + 1;
+            }
+        }
+        0
+    }
+
+    /// Write the first `len` bytes to `writer`, bank by bank
+    pub fn write_to(&self, writer: &mut impl Write, len: usize) -> std::io::Result<()> {
+        for bank in 0..len.div_ceil(BANK_SIZE) {
+            let end = (len - bank * BANK_SIZE).min(BANK_SIZE);
+            writer.write_all(&self.bank(bank)[..end])?;
+        }
+        Ok(())
+    }
+}
+
+impl std::ops::Index<usize> for BankStore {
+    type Output = u8;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        Self::check_bounds(&(index..index + 1));
+        &self.bank(index / BANK_SIZE)[index % BANK_SIZE]
+    }
+}
+
+impl std::ops::IndexMut<usize> for BankStore {
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        Self::check_bounds(&(index..index + 1));
+        &mut self.bank_mut(index / BANK_SIZE)[index % BANK_SIZE]
+    }
+}
+
+impl std::ops::Index<Range<usize>> for BankStore {
+    type Output = [u8];
+
+    /// Slices spanning written and unwritten banks borrow a contiguous copy of the
+    /// memory, built on the first such slice and kept until the next write
+    fn index(&self, index: Range<usize>) -> &Self::Output {
+        Self::check_bounds(&index);
+        if index.is_empty() {
+            return &[];
+        }
+        let banks = Self::banks_of(&index);
+        match self.segment_of(banks.start) {
+            Some((first, data)) if banks.end <= first + data.len() / BANK_SIZE => {
+                let base = first * BANK_SIZE;
+                &data[index.start - base..index.end - base]
+            }
+            None if banks.clone().all(|bank| self.segment_of(bank).is_none()) => {
+                &ZERO_MEMORY[..index.len()]
+            }
+            _ => &self.flat.get_or_init(|| self.read(0..MEMORY_SIZE))[index],
+        }
+    }
+}
+
+impl std::ops::IndexMut<Range<usize>> for BankStore {
+    fn index_mut(&mut self, index: Range<usize>) -> &mut Self::Output {
+        Self::check_bounds(&index);
+        if index.is_empty() {
+            return &mut [];
+        }
+        let banks = Self::banks_of(&index);
+        let base = banks.start * BANK_SIZE;
+        &mut self.banks_mut(banks)[index.start - base..index.end - base]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_banks_are_allocated_on_write() {
+        let mut store = BankStore::new();
+        assert_eq!((store[0x123456], store.allocated_banks()), (0, 0));
+        assert_eq!(store[0x120000..0x120004], [0; 4]);
+
+        store.write(0x00FFFE, &[1, 2, 3, 4]);
+        store.write(0x200000, &[0; BANK_SIZE]);
+        store[0x300010] = 5;
+        assert_eq!(store.allocated_banks(), 3);
+        assert_eq!(store.read(0x00FFFD..0x010003), [0, 1, 2, 3, 4, 0]);
+        assert_eq!(store[0x00FFFE..0x010002], [1, 2, 3, 4]);
+        store[0x010000] = 6;
+        assert_eq!(store[0x00FFFE..0x010002], [1, 2, 6, 4]);
+        assert_eq!(store.used_len(), 0x300011);
+
+        let mut file = Vec::new();
+        store.write_to(&mut file, 0x010002).unwrap();
+        assert_eq!(file, store.read(0..0x010002));
+    }
+
+    #[test]
+    fn test_mutable_slices_merge_banks() {
+        let mut store = BankStore::new();
+        store[0x030000] = 7;
+        store[0x00FFF0..0x010010].fill(1);
+        assert_eq!(store.allocated_banks(), 3);
+        assert_eq!(
+            store[0x00FFEF..0x010011],
+            [&[0][..], &[1; 32], &[0]].concat()
+        );
+
+        // Merging keeps the content of the banks already written
+        store[0x01FFFF..0x030001].fill(2);
+        assert_eq!(store.allocated_banks(), 4);
+        assert_eq!(store[0x010000..0x010010], [1; 16]);
+        assert_eq!(store[0x030000..0x030002], [2, 0]);
+        assert_eq!(store[0x100000..0x200000].len(), 0x100000);
+    }
+
+    #[test]
+    fn test_builders_can_be_shared_between_threads() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<crate::rom_builder::RomBuilder>();
+    }
+
+    #[test]
+    fn test_slices_of_scattered_banks() {
+        let mut store = BankStore::new();
+        store[0x00FFFF] = 1;
+        assert_eq!(store[0x00FFFF..0x010001], [1, 0]);
+        assert_eq!(store.allocated_banks(), 1);
+
+        // The copy is dropped by the next write
+        store[0x010000] = 2;
+        store[0x02FFFF] = 3;
+        assert_eq!(store[0x00FFFF..0x010001], [1, 2]);
+        assert_eq!(store[0x02FFFE..0x030001], [0, 3, 0]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Address 0x1000005 is outside the 16 MiB address space")]
+    fn test_writes_past_the_address_space_panic() {
+        let mut store = BankStore::new();
+        store[0x1000005] = 1;
+    }
+
+    #[test]
+    #[should_panic(expected = "Address 0x1000000 is outside the 16 MiB address space")]
+    fn test_slices_past_the_address_space_panic() {
+        let store = BankStore::new();
+        let _ = &store[0xFFFFFF..0x1000001];
+    }
+}
@@ -1,92 +1,61 @@
 /*
  * Animated Random Noise ROM for BytePusher VM
- * 
+ *
  * This ROM demonstrates animated random noise patterns using a pseudo-random
  * number generator. The screen continuously cycles through 4 pre-generated
  * frames of random noise, creating an animated effect.
- * 
+ *
  * Technical details:
  * - 4 frames of 256x256 pixels (65,536 bytes each)
  * - Linear Congruential Generator (LCG) for random number generation
- * - Animation loop at ~3.75 FPS (4 sync operations per frame)
+ * - Animation loop at ~3.75 FPS (each frame held for 4 frames with wait_frames,
+ *   see --hold-frames)
  * - Total ROM size: ~320KB
  */
 
-use rustedbytes_bytepusher_rombuilder::rom_builder::{RomBuilder, SCREEN_REGISTER_ADDR};
+use clap::Parser;
+use rustedbytes_bytepusher_rombuilder::roms::{NOISE_FRAMES, NOISE_HOLD_FRAMES, animated_noise};
+use rustedbytes_bytepusher_rombuilder::save::PaddingArgs;
 
-fn main() {
-    let mut rm = RomBuilder::new();
-
-    // Memory layout
-    const KERNEL_START: usize = 0x000100;    // Kernel tables (ID and INC)
-    const PROGRAM_START: usize = 0x000300;   // Animation loop code
-    const AUDIO_START: usize = 0x00FF00;     // Audio samples
-    const SCREEN_START: usize = 0x010000;    // Frame data
-    
-    // Initialize registers
-    rm.init_regs(0x0000, PROGRAM_START, SCREEN_START, AUDIO_START);
+#[derive(Parser, Debug)]
+#[command(author, version, about)]
+struct Args {
+    /// Number of frames each noise frame is shown
+    #[arg(long, default_value_t = NOISE_HOLD_FRAMES)]
+    hold_frames: usize,
 
-    // Install kernel tables
-    rm.org(KERNEL_START);
-    rm.install_id_table();
-    rm.install_inc_table();
+    #[command(flatten)]
+    padding: PaddingArgs,
+}
 
-    // ROM logic - Animation loop
-    // This loop cycles through 4 frames, waiting 4 sync periods between each frame
-    // to achieve a slower, more visible animation speed
-    rm.org(PROGRAM_START);
-    
-    // Generate 4 different frames of random noise at compile time
-    // Each frame is 256x256 pixels = 65536 bytes
-    const NUM_FRAMES: usize = 4;
-    
-    // Animation loop: cycle through frames
-    // Each iteration: sync 4 times (for smoother playback) then switch to next frame
-    for frame_idx in 0..NUM_FRAMES {
-        let frame_addr = SCREEN_START + (frame_idx * 65536);
-        
-        // Wait for several frames to slow down animation
-        for _ in 0..4 {
-            rm.sync();
-        }
-        
-        // Set screen register to point to this frame (only write high byte)
-        // Screen register is 1 byte at 0x000005, we must not overwrite audio register at 0x000006
-        rm.cpyi((frame_addr >> 16) as u8, SCREEN_REGISTER_ADDR);
-    }
-    
-    // Jump back to start of animation loop
-    rm.jmp(PROGRAM_START);
+fn main() {
+    let args = Args::parse();
 
-    // No sound - dummy samples
-    rm.org(AUDIO_START).db_arr(&[0; 256]);
+    let rm = animated_noise(args.hold_frames).expect("Failed to build ROM");
 
-    // Generate random noise frames
-    // Uses different seeds for each frame to ensure variety
-    rm.org(SCREEN_START);
-    for frame in 0..NUM_FRAMES {
-        // Use different seed for each frame to ensure variety
-        let mut seed = (frame as u32 * 12345 + 67890) as u64;
-        
-        for _ in 0..65536 {
-            // Simple but effective LCG (Linear Congruential Generator)
-            // Constants from Numerical Recipes: a=1103515245, c=12345
-            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
-            let random_value = ((seed >> 16) & 0xFF) as u8;
-            
-            // Modulo 216 to fit BytePusher's 6x6x6 RGB palette (216 colors)
-            rm.db(random_value % 216);
-        }
+    // Report overlapping writes
+    for overlap in rm.overlaps() {
+        eprintln!("Warning: {}", overlap);
     }
 
-    // Save the ROM file
-    rm.save_to_file("roms/AnimatedNoise.BytePusher")
+    // Save the ROM file on disk
+    let options = args.padding.save_options();
+    rm.save_with_options("roms/AnimatedNoise.BytePusher", &options)
         .expect("Failed to save ROM file");
-    
+
+    // Print the memory layout of the ROM
+    println!("{}", rm.memory_map());
+
     // Print information about the ROM
     println!("Animated Random Noise ROM created!");
-    println!("ROM size: {} bytes", SCREEN_START + NUM_FRAMES * 65536);
-    println!("Number of frames: {}", NUM_FRAMES);
+    println!("ROM size: {} bytes", rm.saved_len(args.padding.policy));
+    println!("Number of frames: {}", NOISE_FRAMES);
     println!("Frame size: 256x256 pixels = 65536 bytes");
-    println!("Expected frame rate: ~60 FPS / 16 syncs = ~3.75 FPS (4 syncs per frame × 4 frames)");
+    println!(
+        "Expected frame rate: ~60 FPS / {} frames = ~{:.2} FPS ({} frames per noise frame × {} noise frames)",
+        args.hold_frames * NOISE_FRAMES,
+        60.0 / (args.hold_frames * NOISE_FRAMES) as f64,
+        args.hold_frames,
+        NOISE_FRAMES
+    );
 }
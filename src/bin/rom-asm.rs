@@ -0,0 +1,87 @@
+use clap::Parser;
+use rustedbytes_bytepusher_rombuilder::asm::assemble_file;
+use rustedbytes_bytepusher_rombuilder::save::PaddingArgs;
+
+#[derive(Parser, Debug)]
+#[command(author, version, about)]
+struct Args {
+    /// Input assembly source (.bpasm)
+    #[arg(short, long)]
+    input: String,
+
+    /// Output ROM file path (default: <input>.BytePusher)
+    #[arg(short, long)]
+    output: Option<String>,
+
+    /// Export the memory map of the ROM as JSON
+    #[arg(long)]
+    map: Option<String>,
+
+    /// Write an assembly listing of the ROM
+    #[arg(long)]
+    listing: Option<String>,
+
+    /// Export labels, sections and tables as an `address name` symbol file
+    #[arg(long)]
+    sym: Option<String>,
+
+    /// Export labels, sections and tables as JSON
+    #[arg(long)]
+    sym_json: Option<String>,
+
+    #[command(flatten)]
+    padding: PaddingArgs,
+}
+
+fn main() {
+    let args = Args::parse();
+
+    let rm = match assemble_file(&args.input) {
+        Ok(rm) => rm,
+        Err(e) => {
+            eprintln!("{}: {}", args.input, e);
+            std::process::exit(1);
+        }
+    };
+
+    // Determine output ROM file name
+    let output_rom = match &args.output {
+        Some(path) => path.clone(),
+        None => {
+            let mut out_path = std::path::PathBuf::from(&args.input);
+            out_path.set_extension("BytePusher");
+            out_path.to_string_lossy().to_string()
+        }
+    };
+
+    // Report overlapping writes
+    for overlap in rm.overlaps() {
+        eprintln!("Warning: {}", overlap);
+    }
+
+    // Save the ROM file on disk
+    let mut options = args.padding.save_options();
+    if let Some(listing_path) = &args.listing {
+        options = options.listing(listing_path);
+    }
+    rm.save_with_options(output_rom.as_str(), &options)
+        .expect("Failed to save ROM file");
+
+    if let Some(map_path) = &args.map {
+        rm.memory_map()
+            .save_json(map_path)
+            .expect("Failed to save memory map");
+    }
+
+    if let Some(sym_path) = &args.sym {
+        rm.symbols()
+            .save_sym(sym_path)
+            .expect("Failed to save symbol file");
+    }
+
+    if let Some(json_path) = &args.sym_json {
+        rm.symbols()
+            .save_json(json_path)
+            .expect("Failed to save symbol file");
+    }
+}
@@ -0,0 +1,55 @@
+use clap::Parser;
+use rustedbytes_bytepusher_rombuilder::disasm::{disassemble, disassemble_with_tables, find_table};
+
+#[derive(Parser, Debug)]
+#[command(author, version, about)]
+struct Args {
+    /// Input ROM file path
+    #[arg(short, long)]
+    rom: String,
+
+    /// Identity table address (default: detected)
+    #[arg(long, value_parser = parse_addr)]
+    id_table: Option<usize>,
+
+    /// Increment table address (default: detected)
+    #[arg(long, value_parser = parse_addr)]
+    inc_table: Option<usize>,
+
+    /// Output listing file path (default: standard output)
+    #[arg(short, long)]
+    output: Option<String>,
+}
+
+/// Parse an address in decimal or 0x-prefixed hexadecimal
+fn parse_addr(text: &str) -> Result<usize, String> {
+    match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => usize::from_str_radix(hex, 16),
+        None => text.parse(),
+    }
+    .map_err(|e| e.to_string())
+}
+
+fn main() {
+    let args = Args::parse();
+
+    let rom = std::fs::read(&args.rom).expect("Failed to load ROM file");
+
+    let disassembly = if args.id_table.is_none() && args.inc_table.is_none() {
+        disassemble(&rom)
+    } else {
+        disassemble_with_tables(
+            &rom,
+            args.id_table.or_else(|| find_table(&rom, |x| x)),
+            args.inc_table
+                .or_else(|| find_table(&rom, |x| x.wrapping_add(1))),
+        )
+    };
+
+    match &args.output {
+        Some(path) => {
+            std::fs::write(path, disassembly.to_string()).expect("Failed to save listing")
+        }
+        None => print!("{}", disassembly),
+    }
+}
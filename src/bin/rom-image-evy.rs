@@ -1,30 +1,34 @@
+use clap::Parser;
+use rustedbytes_bytepusher_rombuilder::save::PaddingArgs;
 use rustedbytes_bytepusher_rombuilder::{
-    image::convert_image_dithered_strength, rom_builder::RomBuilder,
+    image::convert_image_dithered_strength, roms::still_image,
 };
 
-fn main() {
-    let mut rm = RomBuilder::new();
+#[derive(Parser, Debug)]
+#[command(author, version, about)]
+struct Args {
+    #[command(flatten)]
+    padding: PaddingArgs,
+}
 
-    const PROGRAM_START: usize = 0x000100;
-    const AUDIO_START: usize = 0x00FF00;
-    const SCREEN_START: usize = 0x010000;
+fn main() {
+    let args = Args::parse();
 
     let image = convert_image_dithered_strength("resources/images/evy-256x256.png", 1.0)
         .expect("Failed to load image file");
 
-    // Initialize registers
-    rm.init_regs(0x0000, PROGRAM_START, SCREEN_START, AUDIO_START);
+    let rm = still_image(&image).expect("Failed to build ROM");
 
-    // ROM logic
-    rm.org(PROGRAM_START).wait();
-
-    // No sound dummy samples
-    rm.org(AUDIO_START).db_arr(&[0; 256]);
-
-    // Generate random pixels
-    rm.org(SCREEN_START).db_arr(&image);
+    // Report overlapping writes
+    for overlap in rm.overlaps() {
+        eprintln!("Warning: {}", overlap);
+    }
 
     // Save the ROM file on disk
-    rm.save_to_file("roms/Evy.BytePusher")
+    let options = args.padding.save_options();
+    rm.save_with_options("roms/Evy.BytePusher", &options)
         .expect("Failed to save ROM file");
+
+    // Print the memory layout of the ROM
+    println!("{}", rm.memory_map());
 }
@@ -1,7 +1,7 @@
 use clap::Parser;
-use image::RgbImage;
+use rustedbytes_bytepusher_rombuilder::save::PaddingArgs;
 use rustedbytes_bytepusher_rombuilder::{
-    image::convert_image_dithered_strength, rom_builder::RomBuilder,
+    image::convert_image_dithered_strength, render::save_screen_png, roms::still_image,
 };
 
 #[derive(Parser, Debug)]
@@ -22,30 +22,34 @@ struct Args {
     /// Salva una preview PNG dell'immagine convertita (BytePusher)
     #[arg(long)]
     preview: Option<String>,
+
+    /// Export the memory map of the ROM as JSON
+    #[arg(long)]
+    map: Option<String>,
+
+    /// Write an assembly listing of the ROM
+    #[arg(long)]
+    listing: Option<String>,
+
+    /// Export labels, sections and tables as an `address name` symbol file
+    #[arg(long)]
+    sym: Option<String>,
+
+    /// Export labels, sections and tables as JSON
+    #[arg(long)]
+    sym_json: Option<String>,
+
+    #[command(flatten)]
+    padding: PaddingArgs,
 }
 
 fn main() {
     let args = Args::parse();
-    let mut rm = RomBuilder::new();
-
-    const PROGRAM_START: usize = 0x000100;
-    const AUDIO_START: usize = 0x00FF00;
-    const SCREEN_START: usize = 0x010000;
 
     let image = convert_image_dithered_strength(&args.image, args.dithering)
         .expect("Failed to load image file");
 
-    // Initialize registers
-    rm.init_regs(0x0000, PROGRAM_START, SCREEN_START, AUDIO_START);
-
-    // ROM logic
-    rm.org(PROGRAM_START).wait();
-
-    // No sound dummy samples
-    rm.org(AUDIO_START).db_arr(&[0; 256]);
-
-    // Add image data
-    rm.org(SCREEN_START).db_arr(&image);
+    let rm = still_image(&image).expect("Failed to build ROM");
 
     // Determine output ROM file name
     let output_rom = match &args.output {
@@ -60,26 +64,39 @@ fn main() {
         }
     };
 
+    // Report overlapping writes
+    for overlap in rm.overlaps() {
+        eprintln!("Warning: {}", overlap);
+    }
+
     // Save the ROM file on disk
-    rm.save_to_file(output_rom.as_str())
+    let mut options = args.padding.save_options();
+    if let Some(listing_path) = &args.listing {
+        options = options.listing(listing_path);
+    }
+    rm.save_with_options(output_rom.as_str(), &options)
         .expect("Failed to save ROM file");
 
-    // Se richiesto, salva la preview PNG
-    if let Some(preview_path) = &args.preview {
-        save_bytepusher_preview_png(&image, preview_path).expect("Failed to save preview PNG");
+    if let Some(map_path) = &args.map {
+        rm.memory_map()
+            .save_json(map_path)
+            .expect("Failed to save memory map");
+    }
+
+    if let Some(sym_path) = &args.sym {
+        rm.symbols()
+            .save_sym(sym_path)
+            .expect("Failed to save symbol file");
     }
-}
 
-/// Salva una preview PNG a partire dal buffer BytePusher (256x256, palette 216 colori)
-fn save_bytepusher_preview_png(data: &[u8], path: &str) -> Result<(), Box<dyn std::error::Error>> {
-    let palette = rustedbytes_bytepusher_rombuilder::image::build_palette();
-    let mut img = RgbImage::new(256, 256);
-    for (i, &idx) in data.iter().enumerate() {
-        let x = (i % 256) as u32;
-        let y = (i / 256) as u32;
-        img.put_pixel(x, y, palette[idx as usize]);
+    if let Some(json_path) = &args.sym_json {
+        rm.symbols()
+            .save_json(json_path)
+            .expect("Failed to save symbol file");
     }
-    img.save(path)?;
 
-    Ok(())
+    // Se richiesto, salva la preview PNG
+    if let Some(preview_path) = &args.preview {
+        save_screen_png(&image, preview_path).expect("Failed to save preview PNG");
+    }
 }
@@ -1,28 +1,29 @@
-use rustedbytes_bytepusher_rombuilder::rom_builder::RomBuilder;
+use clap::Parser;
+use rustedbytes_bytepusher_rombuilder::roms::random_pattern;
+use rustedbytes_bytepusher_rombuilder::save::PaddingArgs;
 
-fn main() {
-    let mut rm = RomBuilder::new();
-
-    const PROGRAM_START: usize = 0x000100;
-    const AUDIO_START: usize = 0x00FF00;
-    const SCREEN_START: usize = 0x010000;
-
-    // Initialize registers
-    rm.init_regs(0x0000, PROGRAM_START, SCREEN_START, AUDIO_START);
+#[derive(Parser, Debug)]
+#[command(author, version, about)]
+struct Args {
+    #[command(flatten)]
+    padding: PaddingArgs,
+}
 
-    // ROM logic
-    rm.org(PROGRAM_START).wait();
+fn main() {
+    let args = Args::parse();
 
-    // No sound dummy samples
-    rm.org(AUDIO_START).db_arr(&[0; 256]);
+    let rm = random_pattern(&mut rand::rng()).expect("Failed to build ROM");
 
-    // Generate random pixels
-    rm.org(SCREEN_START);
-    for _ in 0..65536 {
-        rm.db(rand::random::<u8>() % 217);
+    // Report overlapping writes
+    for overlap in rm.overlaps() {
+        eprintln!("Warning: {}", overlap);
     }
 
     // Save the ROM file on disk
-    rm.save_to_file("roms/RandomPattern.BytePusher")
+    let options = args.padding.save_options();
+    rm.save_with_options("roms/RandomPattern.BytePusher", &options)
         .expect("Failed to save ROM file");
+
+    // Print the memory layout of the ROM
+    println!("{}", rm.memory_map());
 }
@@ -0,0 +1,84 @@
+use clap::Parser;
+use rustedbytes_bytepusher_rombuilder::{audio, input::InputScript, render, vm::Machine};
+
+#[derive(Parser, Debug)]
+#[command(author, version, about)]
+struct Args {
+    /// Input ROM file path
+    #[arg(short, long)]
+    rom: String,
+
+    /// Number of frames to run
+    #[arg(short, long, default_value_t = 60)]
+    frames: usize,
+
+    /// Keep only one frame every N frames
+    #[arg(long, default_value_t = 1)]
+    step: usize,
+
+    /// Directory where frames are saved as PNG files
+    #[arg(long)]
+    png_dir: Option<String>,
+
+    /// File name prefix of the PNG files
+    #[arg(long, default_value = "screenshot_")]
+    png_prefix: String,
+
+    /// Output animated GIF file path
+    #[arg(long)]
+    gif: Option<String>,
+
+    /// Delay between GIF frames in milliseconds
+    #[arg(long, default_value_t = 17)]
+    gif_delay: u32,
+
+    /// Output WAV file path for the audio samples of every frame
+    #[arg(long)]
+    wav: Option<String>,
+
+    /// Keyboard input script replayed during the run
+    #[arg(short, long)]
+    input: Option<String>,
+}
+
+fn main() {
+    let args = Args::parse();
+    let step = args.step.max(1);
+
+    let mut machine = Machine::from_file(&args.rom).expect("Failed to load ROM file");
+
+    let script = match &args.input {
+        Some(path) => InputScript::load(path).expect("Failed to load input script"),
+        None => InputScript::new(),
+    };
+
+    let mut frames = Vec::new();
+    let mut samples = Vec::new();
+    for frame in 1..=args.frames {
+        script.apply(&mut machine);
+        machine.run_frame();
+
+        if (args.png_dir.is_some() || args.gif.is_some()) && frame % step == 0 {
+            frames.push(render::screen_to_image(machine.screen()));
+        }
+        if args.wav.is_some() {
+            samples.extend_from_slice(machine.audio());
+        }
+    }
+
+    if let Some(png_dir) = &args.png_dir {
+        render::save_png_sequence(&frames, png_dir, &args.png_prefix)
+            .expect("Failed to save PNG frames");
+    }
+
+    if let Some(gif) = &args.gif {
+        render::save_gif(&frames, gif, args.gif_delay * step as u32)
+            .expect("Failed to save GIF file");
+    }
+
+    if let Some(wav) = &args.wav {
+        audio::save_wav(&samples, wav).expect("Failed to save WAV file");
+    }
+
+    println!("Executed {} frames of {}", machine.frame_count(), args.rom);
+}
@@ -1,44 +1,54 @@
-use rustedbytes_bytepusher_rombuilder::{
-    rom_builder::{RomBuilder, SCREEN_REGISTER_ADDR},
-    video::process_png_sequence_flat,
-};
-
-const KERNEL_START: usize = 0x000100;
-const PROGRAM_START: usize = 0x000300;
-const AUDIO_START: usize = 0x00FF00;
-const SCREEN_START: usize = 0x010000;
+use clap::Parser;
+use rustedbytes_bytepusher_rombuilder::roms::{VIDEO_HOLD_FRAMES, video};
+use rustedbytes_bytepusher_rombuilder::save::PaddingArgs;
+use rustedbytes_bytepusher_rombuilder::video::process_png_sequence_flat;
+
+#[derive(Parser, Debug)]
+#[command(author, version, about)]
+struct Args {
+    /// Number of frames each video frame is shown
+    #[arg(long, default_value_t = VIDEO_HOLD_FRAMES)]
+    hold_frames: usize,
+
+    /// Write an assembly listing of the ROM
+    #[arg(long)]
+    listing: Option<String>,
+
+    /// Export labels, sections and tables as an `address name` symbol file
+    #[arg(long)]
+    sym: Option<String>,
+
+    #[command(flatten)]
+    padding: PaddingArgs,
+}
 
 fn main() {
-    let mut rm = RomBuilder::new();
+    let args = Args::parse();
 
-    let video = process_png_sequence_flat("resources/videos/frame_*.png")
+    let frames = process_png_sequence_flat("resources/videos/frame_*.png")
         .expect("Failed to load video frames");
 
-    let frame_count = video.len() / (64 * 1024);
-
-    // Initialize registers
-    rm.init_regs(0x0000, PROGRAM_START, SCREEN_START, AUDIO_START);
+    let rm = video(&frames, args.hold_frames).expect("Failed to build ROM");
 
-    // install kernel tables
-    rm.org(KERNEL_START);
-    rm.install_id_table();
-    rm.install_inc_table();
-
-    // ROM logic
-    rm.org(PROGRAM_START);
-    for _ in 1..frame_count {
-        rm.sync().sync().sync().sync().inc(SCREEN_REGISTER_ADDR);
+    // Report overlapping writes
+    for overlap in rm.overlaps() {
+        eprintln!("Warning: {}", overlap);
     }
-    rm.cpyi(0x01, SCREEN_REGISTER_ADDR);
-    rm.jmp(PROGRAM_START);
-
-    // No sound dummy samples
-    rm.org(AUDIO_START).db_arr(&[0; 256]);
-
-    // Add video frames
-    rm.org(SCREEN_START).db_arr(&video);
 
     // Save the ROM file on disk
-    rm.save_to_file("roms/Catwalk.BytePusher")
+    let mut options = args.padding.save_options();
+    if let Some(listing_path) = &args.listing {
+        options = options.listing(listing_path);
+    }
+    rm.save_with_options("roms/Catwalk.BytePusher", &options)
         .expect("Failed to save ROM file");
+
+    if let Some(sym_path) = &args.sym {
+        rm.symbols()
+            .save_sym(sym_path)
+            .expect("Failed to save symbol file");
+    }
+
+    // Print the memory layout of the ROM
+    println!("{}", rm.memory_map());
 }
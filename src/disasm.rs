@@ -0,0 +1,331 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use crate::rom_builder::{Header, MEMORY_SIZE, PROGRAM_COUNTER_ADDR};
+use crate::rom_opcodes::INSTR_SIZE;
+
+/// Maximum number of instructions followed from the initial program counter
+const MAX_INSTRUCTIONS: usize = 1 << 20;
+
+/// Decoded instruction, recognising the idioms emitted by `RomBuilder`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Nop,
+    Wait,
+    Sync,
+    Jmp(usize),
+    Cpy {
+        source: usize,
+        target: usize,
+    },
+    Cpyi {
+        value: u8,
+        target: usize,
+    },
+    CpyiAddr {
+        value: usize,
+        target: usize,
+    },
+    Inc(usize),
+    Bbj {
+        source: usize,
+        target: usize,
+        jump: usize,
+    },
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Op::Nop => write!(f, "nop"),
+            Op::Wait => write!(f, "wait"),
+            Op::Sync => write!(f, "sync"),
+            Op::Jmp(addr) => write!(f, "jmp 0x{:06X}", addr),
+            Op::Cpy { source, target } => write!(f, "cpy 0x{:06X}, 0x{:06X}", source, target),
+            Op::Cpyi { value, target } => write!(f, "cpyi 0x{:02X}, 0x{:06X}", value, target),
+            Op::CpyiAddr { value, target } => {
+                write!(f, "cpyi_addr 0x{:06X}, 0x{:06X}", value, target)
+            }
+            Op::Inc(addr) => write!(f, "inc 0x{:06X}", addr),
+            Op::Bbj {
+                source,
+                target,
+                jump,
+            } => write!(f, "bbj 0x{:06X}, 0x{:06X}, 0x{:06X}", source, target, jump),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Instruction {
+    pub addr: usize,
+    /// Number of bytes covered, a multiple of 9
+    pub size: usize,
+    pub op: Op,
+}
+
+/// Result of following the control flow of a ROM from its initial program counter
+#[derive(Debug, Clone)]
+pub struct Disassembly {
+    pub header: Header,
+    pub id_table: Option<usize>,
+    pub inc_table: Option<usize>,
+    /// Decoded instructions ordered by address
+    pub instructions: Vec<Instruction>,
+    /// Addresses reached by a jump or loaded into the program counter
+    pub jump_targets: BTreeSet<usize>,
+}
+
+fn read_u24(rom: &[u8], addr: usize) -> usize {
+    let byte = |addr: usize| rom.get(addr).copied().unwrap_or(0) as usize;
+    (byte(addr) << 16) | (byte(addr + 1) << 8) | byte(addr + 2)
+}
+
+/// Find the first 256-bytes aligned table whose entry `x` is `f(x)`
+pub fn find_table(rom: &[u8], f: impl Fn(u8) -> u8) -> Option<usize> {
+    let expected: Vec<u8> = (0..=255).map(f).collect();
+    rom.chunks_exact(256)
+        .position(|page| page == expected.as_slice())
+        .map(|page| page * 256)
+}
+
+struct Decoder<'a> {
+    rom: &'a [u8],
+    id_table: Option<usize>,
+    inc_table: Option<usize>,
+}
+
+impl Decoder<'_> {
+    fn fields(&self, addr: usize) -> (usize, usize, usize) {
+        (
+            read_u24(self.rom, addr),
+            read_u24(self.rom, addr + 3),
+            read_u24(self.rom, addr + 6),
+        )
+    }
+
+    /// Immediate value copied by a cpyi at `addr`, with its target
+    fn cpyi(&self, addr: usize) -> Option<(u8, usize)> {
+        let id_table = self.id_table?;
+        let (source, target, jump) = self.fields(addr);
+        (jump == addr + INSTR_SIZE && (id_table..id_table + 256).contains(&source))
+            .then(|| ((source - id_table) as u8, target))
+    }
+
+    fn cpyi_addr(&self, addr: usize) -> Option<(usize, usize)> {
+        let (hi, target) = self.cpyi(addr)?;
+        let (mid, mid_target) = self.cpyi(addr + INSTR_SIZE)?;
+        let (lo, lo_target) = self.cpyi(addr + 2 * INSTR_SIZE)?;
+        let value = ((hi as usize) << 16) | ((mid as usize) << 8) | lo as usize;
+        (mid_target == target + 1 && lo_target == target + 2).then_some((value, target))
+    }
+
+    fn inc(&self, addr: usize) -> Option<usize> {
+        let inc_table = self.inc_table?;
+        let (source, target, jump) = self.fields(addr);
+        let next = addr + INSTR_SIZE;
+        let (next_source, next_target, next_jump) = self.fields(next);
+        (target == next + 2
+            && jump == next
+            && next_target == source
+            && next_source & !0xFF == inc_table
+            && next_jump == next + INSTR_SIZE)
+            .then_some(source)
+    }
+
+    fn decode(&self, addr: usize) -> Instruction {
+        let instruction = |size: usize, op: Op| Instruction { addr, size, op };
+
+        if let Some((value, target)) = self.cpyi_addr(addr) {
+            let (source, wait_target, jump) = self.fields(addr + 3 * INSTR_SIZE);
+            let wait_addr = addr + 3 * INSTR_SIZE;
+            if target == PROGRAM_COUNTER_ADDR
+                && value == addr + 4 * INSTR_SIZE
+                && (source, wait_target, jump) == (0, 0, wait_addr)
+            {
+                return instruction(4 * INSTR_SIZE, Op::Sync);
+            }
+            return instruction(3 * INSTR_SIZE, Op::CpyiAddr { value, target });
+        }
+
+        if let Some(target) = self.inc(addr) {
+            return instruction(2 * INSTR_SIZE, Op::Inc(target));
+        }
+
+        if let Some((value, target)) = self.cpyi(addr) {
+            return instruction(INSTR_SIZE, Op::Cpyi { value, target });
+        }
+
+        let (source, target, jump) = self.fields(addr);
+        let op = match (source, target, jump) {
+            (0, 0, jump) if jump == addr => Op::Wait,
+            (0, 0, jump) if jump == addr + INSTR_SIZE => Op::Nop,
+            (0, 0, jump) => Op::Jmp(jump),
+            (source, target, jump) if jump == addr + INSTR_SIZE => Op::Cpy { source, target },
+            (source, target, jump) => Op::Bbj {
+                source,
+                target,
+                jump,
+            },
+        };
+        instruction(INSTR_SIZE, op)
+    }
+}
+
+/// Disassemble a ROM, detecting the identity and increment tables
+pub fn disassemble(rom: &[u8]) -> Disassembly {
+    let id_table = find_table(rom, |x| x);
+    let inc_table = find_table(rom, |x| x.wrapping_add(1));
+    disassemble_with_tables(rom, id_table, inc_table)
+}
+
+/// Disassemble a ROM using the given identity and increment table addresses
+pub fn disassemble_with_tables(
+    rom: &[u8],
+    id_table: Option<usize>,
+    inc_table: Option<usize>,
+) -> Disassembly {
+    let header = Header::parse(rom);
+    let decoder = Decoder {
+        rom,
+        id_table,
+        inc_table,
+    };
+
+    let mut instructions = BTreeMap::new();
+    let mut jump_targets = BTreeSet::new();
+    let mut pending = vec![header.program_addr];
+    jump_targets.insert(header.program_addr);
+
+    while let Some(addr) = pending.pop() {
+        if addr >= MEMORY_SIZE
+            || instructions.contains_key(&addr)
+            || instructions.len() >= MAX_INSTRUCTIONS
+        {
+            continue;
+        }
+
+        let instruction = decoder.decode(addr);
+        instructions.insert(addr, instruction);
+
+        let next = addr + instruction.size;
+        match instruction.op {
+            Op::Wait => {}
+            Op::Sync => pending.push(next),
+            Op::Jmp(jump) | Op::Bbj { jump, .. } => {
+                jump_targets.insert(jump);
+                pending.push(jump);
+            }
+            Op::CpyiAddr { value, target } if target == PROGRAM_COUNTER_ADDR => {
+                jump_targets.insert(value);
+                pending.push(value);
+                pending.push(next);
+            }
+            _ => pending.push(next),
+        }
+    }
+
+    Disassembly {
+        header,
+        id_table,
+        inc_table,
+        instructions: instructions.into_values().collect(),
+        jump_targets,
+    }
+}
+
+impl fmt::Display for Disassembly {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let table = |addr: Option<usize>| match addr {
+            Some(addr) => format!("0x{:06X}", addr),
+            None => "not found".to_string(),
+        };
+
+        writeln!(
+            f,
+            "; keyboard 0x{:04X}  program 0x{:06X}  screen 0x{:06X}  audio 0x{:06X}",
+            self.header.keyb_flags,
+            self.header.program_addr,
+            self.header.screen_addr,
+            self.header.audio_addr
+        )?;
+        writeln!(
+            f,
+            "; id table {}  inc table {}",
+            table(self.id_table),
+            table(self.inc_table)
+        )?;
+
+        let mut expected = None;
+        for instruction in &self.instructions {
+            if expected != Some(instruction.addr) {
+                writeln!(f)?;
+                writeln!(f, "        org 0x{:06X}", instruction.addr)?;
+            }
+            if self.jump_targets.contains(&instruction.addr) {
+                writeln!(f, "L{:06X}:", instruction.addr)?;
+            }
+            writeln!(
+                f,
+                "        {:<40} ; {:06X}",
+                instruction.op, instruction.addr
+            )?;
+            expected = Some(instruction.addr + instruction.size);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rom_builder::{MEMORY_SIZE, RomBuilder, SCREEN_REGISTER_ADDR};
+
+    #[test]
+    fn test_disassemble_builder_idioms() {
+        let mut rb = RomBuilder::new();
+        rb.init_regs(0x0000, 0x000300, 0x010000, 0x00FF00);
+        rb.org(0x000100).install_id_table().install_inc_table();
+
+        let skip = rb.new_label("skip");
+        rb.org(0x000300)
+            .sync()
+            .inc(SCREEN_REGISTER_ADDR)
+            .cpyi(0x01, SCREEN_REGISTER_ADDR)
+            .cpy(0x000010, 0x000011)
+            .cpyi_addr(0x123456usize, 0x000020usize)
+            .jmp(skip)
+            .nop();
+        rb.label(skip).nop().wait();
+
+        let disassembly = disassemble(&rb.read(0..MEMORY_SIZE));
+        assert_eq!(disassembly.header.program_addr, 0x000300);
+        assert_eq!(disassembly.id_table, Some(0x000100));
+        assert_eq!(disassembly.inc_table, Some(0x000200));
+
+        let ops: Vec<Op> = disassembly.instructions.iter().map(|i| i.op).collect();
+        assert_eq!(
+            ops,
+            [
+                Op::Sync,
+                Op::Inc(SCREEN_REGISTER_ADDR),
+                Op::Cpyi {
+                    value: 0x01,
+                    target: SCREEN_REGISTER_ADDR
+                },
+                Op::Cpy {
+                    source: 0x000010,
+                    target: 0x000011
+                },
+                Op::CpyiAddr {
+                    value: 0x123456,
+                    target: 0x000020
+                },
+                Op::Jmp(0x000375),
+                Op::Nop,
+                Op::Wait,
+            ]
+        );
+        assert!(disassembly.to_string().contains("L000375:\n        nop"));
+    }
+}
@@ -1,3 +1,6 @@
+use crate::regions::Overlap;
+use crate::tables::Table;
+
 /// Errori che possono verificarsi durante l'elaborazione
 #[derive(Debug)]
 pub enum BytePusherError {
@@ -7,6 +10,34 @@ pub enum BytePusherError {
     IoError(std::io::Error),
     NoFilesFound,
     InvalidFormat,
+    UnresolvedLabels(Vec<String>),
+    ParseError {
+        line: usize,
+        message: String,
+    },
+    Misaligned {
+        what: &'static str,
+        addr: usize,
+        alignment: usize,
+    },
+    TableMissing(Table),
+    TableAlreadyInstalled(Table),
+    AddressOutOfRange(usize),
+    Overlap(Overlap),
+    OutOfMemory {
+        size: usize,
+        alignment: usize,
+    },
+    NotInRoutine,
+    FrameBudgetExceeded {
+        needed: usize,
+        budget: usize,
+    },
+    CountOutOfRange {
+        what: &'static str,
+        count: usize,
+        max: usize,
+    },
 }
 
 impl std::fmt::Display for BytePusherError {
@@ -18,6 +49,68 @@ impl std::fmt::Display for BytePusherError {
             BytePusherError::IoError(e) => write!(f, "IO error: {}", e),
             BytePusherError::NoFilesFound => write!(f, "No files found matching pattern"),
             BytePusherError::InvalidFormat => write!(f, "Invalid format encountered"),
+            BytePusherError::UnresolvedLabels(names) => {
+                write!(f, "Unresolved labels: {}", names.join(", "))
+            }
+            BytePusherError::ParseError { line, message } => {
+                write!(f, "Parse error at line {}: {}", line, message)
+            }
+            BytePusherError::Misaligned {
+                what, alignment, ..
+            } => write!(
+                f,
+                "{} needs to be {}-bytes memory aligned.",
+                what, alignment
+            ),
+            BytePusherError::TableMissing(table) => match table {
+                Table::Id => write!(
+                    f,
+                    "Please install the identity table first using install_id_table()"
+                ),
+                Table::Inc => write!(
+                    f,
+                    "Please install the increment table first using install_inc_table()"
+                ),
+                _ => write!(
+                    f,
+                    "Please install the {} first using install_table(Table::{})",
+                    table.title(),
+                    table.name()
+                ),
+            },
+            BytePusherError::TableAlreadyInstalled(table) => {
+                write!(f, "{} already installed.", table.title())
+            }
+            BytePusherError::AddressOutOfRange(addr) => {
+                write!(
+                    f,
+                    "Address 0x{:X} is outside the 16 MiB address space",
+                    addr
+                )
+            }
+            BytePusherError::Overlap(overlap) => write!(f, "Overlapping write: {}", overlap),
+            BytePusherError::OutOfMemory { size, alignment } => write!(
+                f,
+                "No free space for {} bytes aligned to {} bytes",
+                size, alignment
+            ),
+            BytePusherError::NotInRoutine => {
+                write!(
+                    f,
+                    "ret() used outside of a routine, start one with routine() first"
+                )
+            }
+            BytePusherError::FrameBudgetExceeded { needed, budget } => write!(
+                f,
+                "{} instructions do not fit in a frame budget of {} instructions",
+                needed, budget
+            ),
+            BytePusherError::CountOutOfRange { what, count, max } if *max == usize::MAX => {
+                write!(f, "{} must be at least 1, got {}", what, count)
+            }
+            BytePusherError::CountOutOfRange { what, count, max } => {
+                write!(f, "{} must be between 1 and {}, got {}", what, max, count)
+            }
         }
     }
 }
@@ -18,7 +18,7 @@ pub fn build_palette() -> Vec<Rgb<u8>> {
 }
 
 /// Trova l'indice del colore più vicino nella palette
-fn find_closest_color(pixel: Rgb<u8>, palette: &Vec<Rgb<u8>>) -> u8 {
+fn find_closest_color(pixel: Rgb<u8>, palette: &[Rgb<u8>]) -> u8 {
     let mut min_distance = f64::INFINITY;
     let mut closest_index = 0;
     for (index, palette_color) in palette.iter().enumerate() {
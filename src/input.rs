@@ -0,0 +1,167 @@
+use std::fmt;
+
+use crate::error::BytePusherError;
+use crate::vm::Machine;
+
+/// Scripted keyboard input: the keys held down from a given frame on
+///
+/// The text format has one event per line, a frame number followed by the
+/// 16-bit key mask (bit N is key N) in decimal, `0x` hex or `0b` binary.
+/// Everything after `#` is a comment.
+///
+/// ```text
+/// # frame  keys
+/// 0        0x0000
+/// 60       0x0012   # keys 1 and 4
+/// 90       0
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InputScript {
+    events: Vec<(usize, u16)>,
+}
+
+impl InputScript {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn parse(text: &str) -> Result<Self, BytePusherError> {
+        let mut script = Self::new();
+
+        for (index, line) in text.lines().enumerate() {
+            let error = |message: String| BytePusherError::ParseError {
+                line: index + 1,
+                message,
+            };
+
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() != 2 {
+                return Err(error(format!(
+                    "expected '<frame> <keys>', found '{}'",
+                    line
+                )));
+            }
+
+            let frame = fields[0]
+                .parse::<usize>()
+                .map_err(|_| error(format!("invalid frame number '{}'", fields[0])))?;
+            let keys = parse_mask(fields[1])
+                .ok_or_else(|| error(format!("invalid key mask '{}'", fields[1])))?;
+
+            if script.events.last().is_some_and(|&(last, _)| last >= frame) {
+                return Err(error(format!("frame {} is not in increasing order", frame)));
+            }
+            script.events.push((frame, keys));
+        }
+
+        Ok(script)
+    }
+
+    pub fn load(filename: &str) -> Result<Self, BytePusherError> {
+        Self::parse(&std::fs::read_to_string(filename)?)
+    }
+
+    pub fn save(&self, filename: &str) -> Result<(), BytePusherError> {
+        std::fs::write(filename, self.to_string())?;
+        Ok(())
+    }
+
+    /// Record the keys held down at the given frame,
+    /// only changes with respect to the previous state are stored
+    pub fn record(&mut self, frame: usize, keys: u16) {
+        if let Some(&(last, _)) = self.events.last() {
+            if frame < last {
+                panic!("Input must be recorded in frame order.")
+            }
+            if frame == last {
+                self.events.pop();
+            }
+        }
+
+        if self.keys_at(frame) != keys {
+            self.events.push((frame, keys));
+        }
+    }
+
+    /// Keys held down during the given frame
+    pub fn keys_at(&self, frame: usize) -> u16 {
+        self.events
+            .iter()
+            .take_while(|&&(start, _)| start <= frame)
+            .last()
+            .map_or(0, |&(_, keys)| keys)
+    }
+
+    /// Set the machine keyboard state for the frame it is about to run
+    pub fn apply(&self, machine: &mut Machine) {
+        machine.set_keys(self.keys_at(machine.frame_count()));
+    }
+}
+
+impl fmt::Display for InputScript {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "# frame  keys")?;
+        for (frame, keys) in &self.events {
+            writeln!(f, "{:<8} 0x{:04X}", frame, keys)?;
+        }
+        Ok(())
+    }
+}
+
+fn parse_mask(text: &str) -> Option<u16> {
+    if let Some(hex) = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        u16::from_str_radix(hex, 16).ok()
+    } else if let Some(bin) = text.strip_prefix("0b").or_else(|| text.strip_prefix("0B")) {
+        u16::from_str_radix(bin, 2).ok()
+    } else {
+        text.parse().ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_lookup() {
+        let script = InputScript::parse(
+            "# frame keys\n\
+             10 0x0012 # keys 1 and 4\n\
+             \n\
+             20 0b1\n\
+             30 0\n",
+        )
+        .unwrap();
+
+        assert_eq!(script.keys_at(0), 0x0000);
+        assert_eq!(script.keys_at(10), 0x0012);
+        assert_eq!(script.keys_at(19), 0x0012);
+        assert_eq!(script.keys_at(25), 0x0001);
+        assert_eq!(script.keys_at(1000), 0x0000);
+    }
+
+    #[test]
+    fn test_record_and_replay() {
+        let mut recorded = InputScript::new();
+        for (frame, keys) in [0, 0, 4, 4, 4, 0, 0x8000].into_iter().enumerate() {
+            recorded.record(frame, keys);
+        }
+
+        let replayed = InputScript::parse(&recorded.to_string()).unwrap();
+        assert_eq!(replayed, recorded);
+        assert_eq!(replayed.events, [(2, 4), (5, 0), (6, 0x8000)]);
+    }
+
+    #[test]
+    fn test_parse_error_reports_line() {
+        match InputScript::parse("0 1\n5 zz\n") {
+            Err(BytePusherError::ParseError { line, .. }) => assert_eq!(line, 2),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+}
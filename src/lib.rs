@@ -1,5 +1,25 @@
+pub mod asm;
+pub mod audio;
+pub mod banks;
+pub mod disasm;
 pub mod error;
 pub mod image;
+pub mod input;
+pub mod listing;
+pub mod memory_map;
+pub mod regions;
+pub mod render;
+pub mod rom_arith;
+pub mod rom_branch;
 pub mod rom_builder;
+pub mod rom_call;
+pub mod rom_indirect;
+pub mod rom_logic;
 pub mod rom_opcodes;
+pub mod rom_timing;
+pub mod roms;
+pub mod save;
+pub mod symbols;
+pub mod tables;
 pub mod video;
+pub mod vm;
@@ -0,0 +1,182 @@
+use std::fmt;
+
+use crate::error::BytePusherError;
+use crate::rom_builder::RomBuilder;
+use crate::rom_opcodes::INSTR_SIZE;
+
+/// Bytes produced by one opcode, or a run of raw data
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListingEntry {
+    pub addr: usize,
+    /// Final ROM content, label references included
+    pub bytes: Vec<u8>,
+    /// Opcode that produced the bytes, `None` for data written with `db`/`write_*`
+    pub op: Option<String>,
+    pub section: String,
+}
+
+/// Emitted code of a ROM, with the opcode behind each instruction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Listing {
+    /// Entries ordered by address
+    pub entries: Vec<ListingEntry>,
+    /// Bound labels ordered by address
+    pub labels: Vec<(usize, String)>,
+}
+
+impl Listing {
+    pub fn from_builder(rom: &RomBuilder) -> Self {
+        let mut entries: Vec<ListingEntry> = rom
+            .emissions()
+            .map(|(range, op, section)| ListingEntry {
+                addr: range.start,
+                bytes: rom.read(range),
+                op: op.map(str::to_string),
+                section: section.to_string(),
+            })
+            .collect();
+        entries.sort_by_key(|entry| entry.addr);
+
+        let mut labels: Vec<(usize, String)> = rom
+            .labels()
+            .filter_map(|(name, addr)| addr.map(|addr| (addr, name.to_string())))
+            .collect();
+        labels.sort_by_key(|(addr, _)| *addr);
+
+        Self { entries, labels }
+    }
+
+    pub fn save(&self, filename: &str) -> Result<(), BytePusherError> {
+        std::fs::write(filename, self.to_string())?;
+        Ok(())
+    }
+}
+
+fn write_label(f: &mut fmt::Formatter<'_>, addr: usize, name: &str) -> fmt::Result {
+    writeln!(f, "{:<36}; {:06X}", format!("{}:", name), addr)
+}
+
+fn hex_bytes(bytes: &[u8]) -> String {
+    let hex: Vec<String> = bytes.iter().map(|b| format!("{:02X}", b)).collect();
+    hex.join(" ")
+}
+
+impl fmt::Display for Listing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut labels = self.labels.iter().peekable();
+        let mut section = None;
+        let mut end = None;
+
+        for entry in &self.entries {
+            if section != Some(&entry.section) || end != Some(entry.addr) {
+                if section.is_some() {
+                    writeln!(f)?;
+                }
+                writeln!(f, "; section {}", entry.section)?;
+                section = Some(&entry.section);
+            }
+            end = Some(entry.addr + entry.bytes.len());
+
+            while let Some((addr, name)) = labels.next_if(|(addr, _)| *addr <= entry.addr) {
+                write_label(f, *addr, name)?;
+            }
+
+            match &entry.op {
+                // One row per 9-bytes instruction, the opcode is shown on the first one
+                Some(op) if entry.bytes.len().is_multiple_of(INSTR_SIZE) => {
+                    for (i, chunk) in entry.bytes.chunks(INSTR_SIZE).enumerate() {
+                        let addr = entry.addr + i * INSTR_SIZE;
+                        if i == 0 {
+                            writeln!(f, "{:06X}  {:<28}  {}", addr, hex_bytes(chunk), op)?;
+                        } else {
+                            writeln!(f, "{:06X}  {}", addr, hex_bytes(chunk))?;
+                        }
+                    }
+                }
+                // Data and tables are summarised by their first bytes
+                op => {
+                    let shown = &entry.bytes[..entry.bytes.len().min(INSTR_SIZE)];
+                    let more = if shown.len() < entry.bytes.len() {
+                        ".."
+                    } else {
+                        ""
+                    };
+                    let op = match op {
+                        Some(op) => op.clone(),
+                        None => format!("db ({} bytes)", entry.bytes.len()),
+                    };
+                    writeln!(
+                        f,
+                        "{:06X}  {:<28}  {}",
+                        entry.addr,
+                        hex_bytes(shown) + more,
+                        op
+                    )?;
+                }
+            }
+        }
+
+        for (addr, name) in labels {
+            write_label(f, *addr, name)?;
+        }
+        Ok(())
+    }
+}
+
+impl RomBuilder {
+    /// Emitted instructions and data with the opcode that produced them
+    pub fn listing(&self) -> Listing {
+        Listing::from_builder(self)
+    }
+
+    /// Write the listing of the ROM as a text file, to be kept next to the ROM
+    pub fn save_listing(&self, filename: &str) -> Result<(), BytePusherError> {
+        self.finalize()?;
+        self.listing().save(filename)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_listing_records_opcodes() {
+        let mut rb = RomBuilder::new();
+        rb.init_regs(0x0000, 0x000300, 0x010000, 0x000200);
+        rb.org(0x000100).install_id_table();
+
+        rb.section("program", 0x000300);
+        let start = rb.new_label("start");
+        rb.label(start).sync().jmp(start);
+        rb.section("audio", 0x000200).db_arr(&[0; 256]);
+
+        let listing = rb.listing();
+        let ops: Vec<_> = listing
+            .entries
+            .iter()
+            .map(|e| (e.addr, e.op.as_deref(), e.section.as_str(), e.bytes.len()))
+            .collect();
+        assert_eq!(
+            ops,
+            [
+                (
+                    0x000000,
+                    Some("init_regs 0x0000, 0x000300, 0x010000, 0x000200"),
+                    "registers",
+                    8
+                ),
+                (0x000100, Some("install_id_table"), "id_table", 256),
+                (0x000200, None, "audio", 256),
+                (0x000300, Some("sync"), "program", 36),
+                (0x000324, Some("jmp start"), "program", 9),
+            ]
+        );
+
+        let text = listing.to_string();
+        assert!(text.contains("; section program\nstart:"));
+        assert!(!text.contains("sync.resume"));
+        assert!(text.contains("000324  00 00 00 00 00 00 00 03 00    jmp start\n"));
+        assert!(text.contains("000200  00 00 00 00 00 00 00 00 00..  db (256 bytes)\n"));
+    }
+}
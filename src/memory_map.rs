@@ -0,0 +1,191 @@
+use std::fmt::{self, Write};
+use std::ops::Range;
+
+use crate::error::BytePusherError;
+use crate::rom_builder::{MEMORY_SIZE, RomBuilder};
+
+/// Address range covered by the writes of a section
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SectionInfo {
+    pub name: String,
+    /// First written address
+    pub start: usize,
+    /// Address following the last written byte
+    pub end: usize,
+    /// Number of bytes written
+    pub size: usize,
+    /// Ranges between `start` and `end` not written by this section, either left
+    /// unwritten or written by other sections, see `MemoryMap::free` for unwritten bytes
+    pub gaps: Vec<Range<usize>>,
+}
+
+/// Layout of a ROM within the 16 MiB address space
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryMap {
+    /// Sections ordered by start address
+    pub sections: Vec<SectionInfo>,
+    /// Address ranges not written by any section
+    pub free: Vec<Range<usize>>,
+}
+
+impl MemoryMap {
+    pub fn from_builder(rom: &RomBuilder) -> Self {
+        let mut sections: Vec<SectionInfo> = Vec::new();
+        let mut free = Vec::new();
+        let mut pos = 0;
+
+        for (range, name) in rom.written_regions() {
+            if range.start > pos {
+                free.push(pos..range.start);
+            }
+            pos = range.end;
+
+            match sections.iter_mut().find(|s| s.name == name) {
+                Some(section) => {
+                    if range.start > section.end {
+                        section.gaps.push(section.end..range.start);
+                    }
+                    section.end = range.end;
+                    section.size += range.len();
+                }
+                None => sections.push(SectionInfo {
+                    name: name.to_string(),
+                    start: range.start,
+                    end: range.end,
+                    size: range.len(),
+                    gaps: Vec::new(),
+                }),
+            }
+        }
+        if pos < MEMORY_SIZE {
+            free.push(pos..MEMORY_SIZE);
+        }
+
+        Self { sections, free }
+    }
+
+    pub fn to_json(&self) -> String {
+        fn ranges(ranges: &[Range<usize>]) -> String {
+            let items: Vec<String> = ranges
+                .iter()
+                .map(|r| format!("{{\"start\": {}, \"end\": {}}}", r.start, r.end))
+                .collect();
+            format!("[{}]", items.join(", "))
+        }
+
+        let mut json = String::from("{\n  \"sections\": [\n");
+        for (i, section) in self.sections.iter().enumerate() {
+            let _ = write!(
+                json,
+                "    {{\"name\": \"{}\", \"start\": {}, \"end\": {}, \"size\": {}, \"gaps\": {}}}",
+                json_escape(&section.name),
+                section.start,
+                section.end,
+                section.size,
+                ranges(&section.gaps)
+            );
+            json.push_str(if i + 1 < self.sections.len() {
+                ",\n"
+            } else {
+                "\n"
+            });
+        }
+        let _ = write!(json, "  ],\n  \"free\": {}\n}}\n", ranges(&self.free));
+        json
+    }
+
+    pub fn save_json(&self, filename: &str) -> Result<(), BytePusherError> {
+        std::fs::write(filename, self.to_json())?;
+        Ok(())
+    }
+}
+
+impl fmt::Display for MemoryMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<24} {:<8} {:<8} {:>8}",
+            "Section", "Start", "End", "Size"
+        )?;
+        for section in &self.sections {
+            writeln!(
+                f,
+                "{:<24} {:06X}   {:06X}   {:>8}",
+                section.name, section.start, section.end, section.size
+            )?;
+            for gap in &section.gaps {
+                writeln!(
+                    f,
+                    "  (gap)                  {:06X}   {:06X}",
+                    gap.start, gap.end
+                )?;
+            }
+        }
+
+        let free: usize = self.free.iter().map(|r| r.len()).sum();
+        writeln!(f, "Free: {} bytes in {} ranges", free, self.free.len())
+    }
+}
+
+/// Escape a string to be used inside a JSON string literal
+pub(crate) fn json_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(escaped, "\\u{:04x}", c as u32);
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+impl RomBuilder {
+    /// Sections, their gaps and the free space of the ROM
+    pub fn memory_map(&self) -> MemoryMap {
+        MemoryMap::from_builder(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_map_sections_and_gaps() {
+        let mut rb = RomBuilder::new();
+        rb.init_regs(0x0000, 0x000100, 0x010000, 0x00FF00);
+        rb.section("program", 0x000100).wait();
+        rb.section("audio", 0x00FF00).db_arr(&[0; 256]);
+        rb.section("program", 0x000200).nop();
+
+        let map = rb.memory_map();
+        let names: Vec<_> = map.sections.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["registers", "program", "audio"]);
+
+        let program = &map.sections[1];
+        assert_eq!(
+            (program.start, program.end, program.size),
+            (0x000100, 0x000209, 18)
+        );
+        assert_eq!(program.gaps.len(), 1);
+        assert_eq!(program.gaps[0], 0x000109..0x000200);
+        assert_eq!(
+            map.free,
+            [
+                0x000008..0x000100,
+                0x000109..0x000200,
+                0x000209..0x00FF00,
+                0x010000..MEMORY_SIZE
+            ]
+        );
+
+        let json = map.to_json();
+        assert!(json.contains(
+            "{\"name\": \"audio\", \"start\": 65280, \"end\": 65536, \"size\": 256, \"gaps\": []}"
+        ));
+    }
+}
@@ -0,0 +1,228 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::Range;
+
+/// Two writes hitting the same ROM bytes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Overlap {
+    /// Owner of the write that hit already written bytes
+    pub owner: String,
+    /// Owner of the bytes that were overwritten
+    pub previous_owner: String,
+    pub range: Range<usize>,
+}
+
+impl fmt::Display for Overlap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "'{}' overwrites '{}' at 0x{:06X}..0x{:06X}",
+            self.owner, self.previous_owner, self.range.start, self.range.end
+        )
+    }
+}
+
+/// What the builder does when a write hits bytes already written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverlapPolicy {
+    /// Record the overlap silently, callers report `overlaps()` as they see fit
+    #[default]
+    Allow,
+    /// Record the overlap and print a warning on stderr
+    Warn,
+    /// Refuse the write
+    Deny,
+}
+
+/// Written ROM byte ranges, each tagged with the owner that wrote it
+#[derive(Debug, Clone, Default)]
+pub struct RegionMap {
+    owners: Vec<String>,
+    /// Non overlapping ranges indexed by start: (end, owner id)
+    regions: BTreeMap<usize, (usize, usize)>,
+}
+
+impl RegionMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Id of the owner with the given name, registering it if needed
+    pub fn owner_id(&mut self, name: &str) -> usize {
+        match self.owners.iter().position(|owner| owner == name) {
+            Some(id) => id,
+            None => {
+                self.owners.push(name.to_string());
+                self.owners.len() - 1
+            }
+        }
+    }
+
+    pub fn owner_name(&self, id: usize) -> &str {
+        &self.owners[id]
+    }
+
+    /// Already written parts of `range` with their owner, in address order
+    pub fn find_overlaps(&self, range: Range<usize>) -> Vec<(Range<usize>, usize)> {
+        let mut found = Vec::new();
+        for (&start, &(end, owner)) in self.regions.range(..range.end).rev() {
+            if end <= range.start {
+                break;
+            }
+            found.push((start.max(range.start)..end.min(range.end), owner));
+        }
+        found.reverse();
+        found
+    }
+
+    /// Mark `range` as written by `owner`, bytes already written keep their owner
+    pub fn mark(&mut self, range: Range<usize>, owner: usize) {
+        let mut pos = range.start;
+        for (written, _) in self.find_overlaps(range.clone()) {
+            if written.start > pos {
+                self.insert(pos..written.start, owner);
+            }
+            pos = pos.max(written.end);
+        }
+        if pos < range.end {
+            self.insert(pos..range.end, owner);
+        }
+    }
+
+    /// Mark `range` as written by `owner`, taking it over from its previous owners
+    pub fn assign(&mut self, range: Range<usize>, owner: usize) {
+        for (written, previous_owner) in self.find_overlaps(range.clone()) {
+            let start = *self.regions.range(..written.end).next_back().unwrap().0;
+            let (end, _) = self.regions.remove(&start).unwrap();
+            if start < written.start {
+                self.regions.insert(start, (written.start, previous_owner));
+            }
+            if written.end < end {
+                self.regions.insert(written.end, (end, previous_owner));
+            }
+        }
+        self.insert(range, owner);
+    }
+
+    fn insert(&mut self, range: Range<usize>, owner: usize) {
+        let mut start = range.start;
+        let mut end = range.end;
+
+        if let Some((&prev_start, &(prev_end, prev_owner))) =
+            self.regions.range(..start).next_back()
+            && prev_end == start
+            && prev_owner == owner
+        {
+            start = prev_start;
+        }
+
+        if let Some(&(next_end, next_owner)) = self.regions.get(&end)
+            && next_owner == owner
+        {
+            self.regions.remove(&end);
+            end = next_end;
+        }
+
+        self.regions.insert(start, (end, owner));
+    }
+
+    /// Written ranges with their owner name, in address order
+    pub fn iter(&self) -> impl Iterator<Item = (Range<usize>, &str)> {
+        self.regions
+            .iter()
+            .map(|(&start, &(end, owner))| (start..end, self.owner_name(owner)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rom_builder::RomBuilder;
+
+    #[test]
+    fn test_region_map_merges_and_finds_overlaps() {
+        let mut map = RegionMap::new();
+        let code = map.owner_id("code");
+        let data = map.owner_id("data");
+
+        map.mark(0x100..0x103, code);
+        map.mark(0x103..0x109, code);
+        map.mark(0x200..0x300, data);
+        map.mark(0x2F0..0x310, code);
+
+        let regions: Vec<_> = map.iter().collect();
+        assert_eq!(
+            regions,
+            [
+                (0x100..0x109, "code"),
+                (0x200..0x300, "data"),
+                (0x300..0x310, "code")
+            ]
+        );
+        assert_eq!(
+            map.find_overlaps(0x108..0x201),
+            [(0x108..0x109, code), (0x200..0x201, data)]
+        );
+
+        map.assign(0x280..0x308, code);
+        let regions: Vec<_> = map.iter().collect();
+        assert_eq!(
+            regions,
+            [
+                (0x100..0x109, "code"),
+                (0x200..0x280, "data"),
+                (0x280..0x310, "code")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_builder_reports_overlapping_writes() {
+        let mut rb = RomBuilder::new();
+        rb.set_overlap_policy(OverlapPolicy::Allow);
+
+        rb.org(0x000100).owner("program").db_arr(&[1; 16]);
+        rb.org(0x000108).owner("audio").db_arr(&[2; 16]);
+
+        assert_eq!(
+            rb.overlaps(),
+            [Overlap {
+                owner: "audio".to_string(),
+                previous_owner: "program".to_string(),
+                range: 0x000108..0x000110,
+            }]
+        );
+
+        rb.set_overlap_policy(OverlapPolicy::Deny);
+        assert!(rb.org(0x000100).try_write_u8(3).is_err());
+        assert_eq!(rb[0x000100], 1);
+    }
+
+    #[test]
+    fn test_fixups_and_slices_report_overlaps() {
+        let mut rb = RomBuilder::new();
+        let target = rb.new_label("target");
+        rb.org(0x000100).owner("program").write_addr(target);
+        rb.org(0x000101).owner("data").db(7);
+        // The bytes keep their first owner, patching them is not reported again
+        rb.org(0x000200).owner("program").label(target);
+        rb.owner("patch");
+        rb[0x000102] = 9;
+        rb[0x0000FF..0x000101].copy_from_slice(&[1, 2]);
+
+        let overlap = |owner: &str, previous_owner: &str, range| Overlap {
+            owner: owner.to_string(),
+            previous_owner: previous_owner.to_string(),
+            range,
+        };
+        assert_eq!(
+            rb.overlaps(),
+            [
+                overlap("data", "program", 0x000101..0x000102),
+                overlap("patch", "program", 0x000102..0x000103),
+                overlap("patch", "program", 0x000100..0x000101),
+            ]
+        );
+        assert_eq!(rb[0x0000FF..0x000103], [1, 2, 0x02, 9]);
+    }
+}
@@ -0,0 +1,97 @@
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::PathBuf;
+
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, DynamicImage, Frame, Rgb, RgbImage};
+
+use crate::error::BytePusherError;
+use crate::image::build_palette;
+use crate::vm::{Machine, SCREEN_SIZE};
+
+/// Convert a screen page (256x256 palette indices) into an RGB image
+/// Indices outside the 216 colors palette are rendered black
+pub fn screen_to_image(screen: &[u8]) -> RgbImage {
+    let palette = build_palette();
+    let mut img = RgbImage::new(256, 256);
+    for (i, &idx) in screen.iter().take(SCREEN_SIZE).enumerate() {
+        let x = (i % 256) as u32;
+        let y = (i / 256) as u32;
+        let color = palette.get(idx as usize).copied().unwrap_or(Rgb([0, 0, 0]));
+        img.put_pixel(x, y, color);
+    }
+    img
+}
+
+/// Save a screen page as PNG file
+pub fn save_screen_png(screen: &[u8], path: &str) -> Result<(), BytePusherError> {
+    screen_to_image(screen).save(path)?;
+    Ok(())
+}
+
+/// Run the machine for the given number of frames,
+/// returning the screen displayed at the end of each frame
+pub fn render_frames(machine: &mut Machine, frames: usize) -> Vec<RgbImage> {
+    let mut images = Vec::with_capacity(frames);
+    for _ in 0..frames {
+        machine.run_frame();
+        images.push(screen_to_image(machine.screen()));
+    }
+    images
+}
+
+/// Save frames as `<prefix>0001.png`, `<prefix>0002.png`, ... inside `output_dir`
+pub fn save_png_sequence(
+    frames: &[RgbImage],
+    output_dir: &str,
+    prefix: &str,
+) -> Result<Vec<PathBuf>, BytePusherError> {
+    fs::create_dir_all(output_dir)?;
+
+    let mut paths = Vec::with_capacity(frames.len());
+    for (i, frame) in frames.iter().enumerate() {
+        let mut path = PathBuf::from(output_dir);
+        path.push(format!("{}{:04}.png", prefix, i + 1));
+        frame.save(&path)?;
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+/// Save frames as an endlessly looping animated GIF
+pub fn save_gif(
+    frames: &[RgbImage],
+    path: &str,
+    frame_delay_ms: u32,
+) -> Result<(), BytePusherError> {
+    let file = BufWriter::new(File::create(path)?);
+    let mut encoder = GifEncoder::new(file);
+    encoder.set_repeat(Repeat::Infinite)?;
+
+    let delay = Delay::from_numer_denom_ms(frame_delay_ms, 1);
+    encoder.encode_frames(frames.iter().map(|frame| {
+        let rgba = DynamicImage::ImageRgb8(frame.clone()).to_rgba8();
+        Frame::from_parts(rgba, 0, 0, delay)
+    }))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_screen_to_image_uses_palette() {
+        let mut screen = vec![0u8; SCREEN_SIZE];
+        screen[1] = 215;
+        screen[256] = 180;
+        screen[257] = 250;
+
+        let img = screen_to_image(&screen);
+        assert_eq!(img.dimensions(), (256, 256));
+        assert_eq!(img.get_pixel(0, 0), &Rgb([0, 0, 0]));
+        assert_eq!(img.get_pixel(1, 0), &Rgb([255, 255, 255]));
+        assert_eq!(img.get_pixel(0, 1), &Rgb([255, 0, 0]));
+        assert_eq!(img.get_pixel(1, 1), &Rgb([0, 0, 0]));
+    }
+}
@@ -0,0 +1,498 @@
+use crate::error::BytePusherError;
+use crate::rom_builder::RomBuilder;
+use crate::rom_opcodes::INSTR_SIZE;
+use crate::tables::Table;
+
+// Table-driven arithmetic on bytes, all results wrap around at 256
+// Tables that are not installed yet are placed after the existing content on first use
+//
+// Multi-byte values are stored most significant byte first, like addresses.
+// Carries are 0/1 bytes found with the zero-detection table (or the Lt table for
+// additions), and are applied without branches: the byte and its increment are
+// copied to a 256-bytes aligned scratch page, then the carry selects one of them.
+
+impl RomBuilder {
+    /// Decrement the byte at the provided address, wrapping at 0
+    /// 18 bytes are written to the ROM
+    pub fn dec(&mut self, addr: usize) -> &mut Self {
+        self.try_dec(addr).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_dec(&mut self, addr: usize) -> Result<&mut Self, BytePusherError> {
+        self.try_unary_op("dec", Table::Dec, addr)
+    }
+
+    /// Add an immediate value to the byte at the provided address
+    /// 18 bytes are written to the ROM
+    pub fn add_imm(&mut self, addr: usize, value: u8) -> &mut Self {
+        self.try_add_imm(addr, value)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_add_imm(&mut self, addr: usize, value: u8) -> Result<&mut Self, BytePusherError> {
+        self.try_binary_imm_op("add_imm", Table::Add, addr, value)
+    }
+
+    /// Add the byte at address `b` to the byte at address `a`
+    /// 27 bytes are written to the ROM
+    pub fn add(&mut self, a: usize, b: usize) -> &mut Self {
+        self.try_add(a, b).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_add(&mut self, a: usize, b: usize) -> Result<&mut Self, BytePusherError> {
+        self.try_binary_op("add", Table::Add, a, b)
+    }
+
+    /// Subtract the byte at address `b` from the byte at address `a`
+    /// 27 bytes are written to the ROM
+    pub fn sub(&mut self, a: usize, b: usize) -> &mut Self {
+        self.try_sub(a, b).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_sub(&mut self, a: usize, b: usize) -> Result<&mut Self, BytePusherError> {
+        self.try_binary_op("sub", Table::Sub, a, b)
+    }
+
+    /// Increment the 16-bit value at the provided address
+    /// 81 bytes are written to the ROM
+    pub fn inc16(&mut self, addr: usize) -> &mut Self {
+        self.try_inc16(addr).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_inc16(&mut self, addr: usize) -> Result<&mut Self, BytePusherError> {
+        self.try_inc_wide("inc16", addr, 2)
+    }
+
+    /// Increment the 24-bit value at the provided address, e.g. a frame pointer
+    /// 171 bytes are written to the ROM
+    pub fn inc24(&mut self, addr: usize) -> &mut Self {
+        self.try_inc24(addr).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_inc24(&mut self, addr: usize) -> Result<&mut Self, BytePusherError> {
+        self.try_inc_wide("inc24", addr, 3)
+    }
+
+    /// Decrement the 16-bit value at the provided address
+    /// 81 bytes are written to the ROM
+    pub fn dec16(&mut self, addr: usize) -> &mut Self {
+        self.try_dec16(addr).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_dec16(&mut self, addr: usize) -> Result<&mut Self, BytePusherError> {
+        self.check_space(9 * INSTR_SIZE)?;
+        let dec_table_addr = self.require_table(Table::Dec)?;
+        let zero_table_addr = self.require_table(Table::Zero)?;
+        let page = self.require_carry_page()?;
+
+        let carry = page + 2;
+        self.emit(
+            |_| format!("dec16 0x{:06X}", addr),
+            |rb| -> Result<(), BytePusherError> {
+                // The low byte borrows when it is zero before the decrement
+                rb.zero_lookup(zero_table_addr, addr + 1, carry)?;
+                let lookup = rb.get_next_instr_addr() + 2;
+                rb.try_cpy(addr + 1, lookup)?
+                    .try_cpy(dec_table_addr, addr + 1)?
+                    .apply_carry(dec_table_addr, page, addr)?;
+                Ok(())
+            },
+        )?;
+        Ok(self)
+    }
+
+    /// Add the 16-bit value at address `b` to the 16-bit value at address `a`
+    /// 135 bytes are written to the ROM
+    pub fn add16(&mut self, a: usize, b: usize) -> &mut Self {
+        self.try_add16(a, b).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_add16(&mut self, a: usize, b: usize) -> Result<&mut Self, BytePusherError> {
+        self.check_space(15 * INSTR_SIZE)?;
+        let inc_table_addr = self.require_table(Table::Inc)?;
+        let add_table_addr = self.require_table(Table::Add)?;
+        let lt_table_addr = self.require_table(Table::Lt)?;
+        let page = self.require_carry_page()?;
+
+        let carry = page + 2;
+        self.emit(
+            |_| format!("add16 0x{:06X}, 0x{:06X}", a, b),
+            |rb| -> Result<(), BytePusherError> {
+                // The low byte carries when the sum wraps below its previous value
+                rb.try_cpy(a + 1, carry)?
+                    .binary_lookup(add_table_addr, a + 1, b + 1, a + 1)?
+                    .binary_lookup(lt_table_addr, a + 1, carry, carry)?
+                    .binary_lookup(add_table_addr, a, b, a)?
+                    .apply_carry(inc_table_addr, page, a)?;
+                Ok(())
+            },
+        )?;
+        Ok(self)
+    }
+
+    /// Increment the value of `len` bytes at the provided address
+    fn try_inc_wide(
+        &mut self,
+        op: &str,
+        addr: usize,
+        len: usize,
+    ) -> Result<&mut Self, BytePusherError> {
+        self.check_space((10 * len - 11) * INSTR_SIZE)?;
+        let inc_table_addr = self.require_table(Table::Inc)?;
+        let zero_table_addr = self.require_table(Table::Zero)?;
+        let page = self.require_carry_page()?;
+
+        let carry = page + 2;
+        let low = addr + len - 1;
+        self.emit(
+            |_| format!("{} 0x{:06X}", op, addr),
+            |rb| -> Result<(), BytePusherError> {
+                // The low byte carries when it wraps to zero
+                let lookup = rb.get_next_instr_addr() + 2;
+                rb.try_cpy(low, lookup)?
+                    .try_cpy(inc_table_addr, low)?
+                    .zero_lookup(zero_table_addr, low, carry)?;
+                for byte in (addr..low).rev() {
+                    rb.apply_carry(inc_table_addr, page, byte)?;
+                    if byte > addr {
+                        // The carry goes on only if this byte wrapped to zero too
+                        rb.try_cpy(zero_table_addr + 1, page)?
+                            .zero_lookup(zero_table_addr, byte, page + 1)?
+                            .select(page, carry, carry)?;
+                    }
+                }
+                Ok(())
+            },
+        )?;
+        Ok(self)
+    }
+
+    /// Base address of the carry scratch page: two selection candidates and the carry
+    pub(crate) fn require_carry_page(&mut self) -> Result<usize, BytePusherError> {
+        if let Some(page) = self.carry_page {
+            return Ok(page);
+        }
+        let page = self.alloc_top(3, 256)?;
+        self.carry_page = Some(page);
+        Ok(page)
+    }
+
+    /// Copy 1 to `target` if the byte at the provided address is zero, 0 otherwise
+    /// 18 bytes are written to the ROM
+    pub(crate) fn zero_lookup(
+        &mut self,
+        zero_table_addr: usize,
+        addr: usize,
+        target: usize,
+    ) -> Result<&mut Self, BytePusherError> {
+        let lookup = self.get_next_instr_addr() + 2;
+        self.try_cpy(addr, lookup)?.try_cpy(zero_table_addr, target)
+    }
+
+    /// Copy `page[selector]` to `target`, where selector is the byte (0 or 1) at the provided address
+    /// 18 bytes are written to the ROM
+    pub(crate) fn select(
+        &mut self,
+        page: usize,
+        selector: usize,
+        target: usize,
+    ) -> Result<&mut Self, BytePusherError> {
+        let lookup = self.get_next_instr_addr() + 2;
+        self.try_cpy(selector, lookup)?.try_cpy(page, target)
+    }
+
+    /// Replace the byte at the provided address with `step_table[x]` if the carry is set
+    /// 45 bytes are written to the ROM
+    fn apply_carry(
+        &mut self,
+        step_table_addr: usize,
+        page: usize,
+        addr: usize,
+    ) -> Result<&mut Self, BytePusherError> {
+        self.try_cpy(addr, page)?;
+        let lookup = self.get_next_instr_addr() + 2;
+        self.try_cpy(addr, lookup)?
+            .try_cpy(step_table_addr, page + 1)?
+            .select(page, page + 2, addr)
+    }
+
+    /// Store `table[x]` into `x`, the lookup source address is patched with the operand
+    /// 18 bytes are written to the ROM
+    pub(crate) fn try_unary_op(
+        &mut self,
+        op: &str,
+        table: Table,
+        addr: usize,
+    ) -> Result<&mut Self, BytePusherError> {
+        self.check_space(18)?;
+        let table_addr = self.require_table(table)?;
+
+        self.emit(
+            |_| format!("{} 0x{:06X}", op, addr),
+            |rb| {
+                let lookup = rb.get_next_instr_addr() + 2;
+                rb.try_cpy(addr, lookup)?.try_cpy(table_addr, addr)?;
+                Ok::<(), BytePusherError>(())
+            },
+        )?;
+        Ok(self)
+    }
+
+    /// Store `table[a][value]` into `a`, the row is selected by patching the
+    /// middle byte of the lookup source address
+    /// 18 bytes are written to the ROM
+    pub(crate) fn try_binary_imm_op(
+        &mut self,
+        op: &str,
+        table: Table,
+        addr: usize,
+        value: u8,
+    ) -> Result<&mut Self, BytePusherError> {
+        self.check_space(18)?;
+        let table_addr = self.require_table(table)?;
+
+        self.emit(
+            |_| format!("{} 0x{:06X}, 0x{:02X}", op, addr, value),
+            |rb| {
+                let row = rb.get_next_instr_addr() + 1;
+                rb.try_cpy(addr, row)?
+                    .try_cpy(table_addr + value as usize, addr)?;
+                Ok::<(), BytePusherError>(())
+            },
+        )?;
+        Ok(self)
+    }
+
+    /// Store `table[a][b]` into `a`, the lookup source address is patched with both operands
+    /// 27 bytes are written to the ROM
+    pub(crate) fn try_binary_op(
+        &mut self,
+        op: &str,
+        table: Table,
+        a: usize,
+        b: usize,
+    ) -> Result<&mut Self, BytePusherError> {
+        self.check_space(27)?;
+        let table_addr = self.require_table(table)?;
+
+        self.emit(
+            |_| format!("{} 0x{:06X}, 0x{:06X}", op, a, b),
+            |rb| rb.binary_lookup(table_addr, a, b, a).map(|_| ()),
+        )?;
+        Ok(self)
+    }
+
+    /// Copy `table[a][b]` to `target`
+    /// 27 bytes are written to the ROM
+    pub(crate) fn binary_lookup(
+        &mut self,
+        table_addr: usize,
+        a: usize,
+        b: usize,
+        target: usize,
+    ) -> Result<&mut Self, BytePusherError> {
+        let lookup = self.get_current_addr() + 2 * INSTR_SIZE;
+        self.try_cpy(a, lookup + 1)?
+            .try_cpy(b, lookup + 2)?
+            .try_cpy(table_addr, target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::BytePusherError;
+    use crate::regions::OverlapPolicy;
+    use crate::rom_builder::{MEMORY_SIZE, RomBuilder};
+    use crate::save::PaddingPolicy;
+    use crate::tables::Table;
+    use crate::vm::testing::{program, run};
+
+    const CELLS: usize = 0x000080;
+
+    /// Run the code emitted by `f` on `data` stored at `CELLS`, returning the data after one frame
+    fn run_on(data: &[u8], f: impl FnOnce(&mut RomBuilder)) -> Vec<u8> {
+        let mut rb = program();
+        rb.with_owner("data", |rb| {
+            rb.org(CELLS).db_arr(data);
+        });
+        rb.org(0x000100);
+        f(&mut rb);
+        run(&mut rb)[CELLS..CELLS + data.len()].to_vec()
+    }
+
+    #[test]
+    fn test_dec_wraps_around() {
+        let cells = run_on(&[0x00, 0x01, 0x80], |rb| {
+            rb.dec(CELLS).dec(CELLS + 1).dec(CELLS + 2);
+        });
+        assert_eq!(cells, [0xFF, 0x00, 0x7F]);
+    }
+
+    #[test]
+    fn test_add_imm_wraps_around() {
+        let cells = run_on(&[0x10, 0xF0, 0xFF], |rb| {
+            rb.add_imm(CELLS, 0x25)
+                .add_imm(CELLS + 1, 0x25)
+                .add_imm(CELLS + 2, 0x00);
+        });
+        assert_eq!(cells, [0x35, 0x15, 0xFF]);
+    }
+
+    #[test]
+    fn test_add_wraps_around() {
+        let cells = run_on(&[0xF0, 0x20, 0x90, 0x01], |rb| {
+            rb.add(CELLS, CELLS + 1)
+                .add(CELLS + 2, CELLS + 2)
+                .add(CELLS + 3, CELLS + 3);
+        });
+        assert_eq!(cells, [0x10, 0x20, 0x20, 0x02]);
+    }
+
+    #[test]
+    fn test_sub_wraps_around() {
+        let cells = run_on(&[0x05, 0x07, 0x07, 0x42], |rb| {
+            rb.sub(CELLS, CELLS + 1)
+                .sub(CELLS + 2, CELLS + 1)
+                .sub(CELLS + 3, CELLS + 3);
+        });
+        assert_eq!(cells, [0xFE, 0x07, 0x00, 0x00]);
+    }
+
+    #[test]
+    fn test_inc16_carries_and_wraps_around() {
+        let cells = run_on(&[0x00, 0xFF, 0xFF, 0xFF, 0x12, 0x34], |rb| {
+            rb.inc16(CELLS).inc16(CELLS + 2).inc16(CELLS + 4);
+        });
+        assert_eq!(cells, [0x01, 0x00, 0x00, 0x00, 0x12, 0x35]);
+    }
+
+    #[test]
+    fn test_inc24_carries_and_wraps_around() {
+        let cells = run_on(
+            &[
+                0x00, 0xFF, 0xFF, 0x12, 0xFF, 0x34, 0x12, 0x00, 0xFF, 0xFF, 0xFF, 0xFF,
+            ],
+            |rb| {
+                for i in 0..4 {
+                    rb.inc24(CELLS + 3 * i);
+                }
+            },
+        );
+        assert_eq!(
+            cells,
+            [
+                0x01, 0x00, 0x00, 0x12, 0xFF, 0x35, 0x12, 0x01, 0x00, 0x00, 0x00, 0x00
+            ]
+        );
+    }
+
+    #[test]
+    fn test_dec16_borrows_and_wraps_around() {
+        let cells = run_on(&[0x01, 0x00, 0x00, 0x00, 0x12, 0x34], |rb| {
+            rb.dec16(CELLS).dec16(CELLS + 2).dec16(CELLS + 4);
+        });
+        assert_eq!(cells, [0x00, 0xFF, 0xFF, 0xFF, 0x12, 0x33]);
+    }
+
+    #[test]
+    fn test_add16_carries_and_wraps_around() {
+        let cells = run_on(&[0x12, 0xF0, 0x01, 0x20, 0xFF, 0xFF, 0x00, 0x01], |rb| {
+            rb.add16(CELLS, CELLS + 2).add16(CELLS + 4, CELLS + 6);
+        });
+        assert_eq!(cells, [0x14, 0x10, 0x01, 0x20, 0x00, 0x00, 0x00, 0x01]);
+    }
+
+    #[test]
+    fn test_add16_doubles_a_value_added_to_itself() {
+        let cells = run_on(&[0x40, 0x80, 0x7F, 0x80, 0xFF, 0xFF, 0x00, 0x7F], |rb| {
+            for i in 0..4 {
+                rb.add16(CELLS + 2 * i, CELLS + 2 * i);
+            }
+        });
+        assert_eq!(cells, [0x81, 0x00, 0xFF, 0x00, 0xFF, 0xFE, 0x00, 0xFE]);
+    }
+
+    #[test]
+    fn test_lazy_tables_follow_the_code() {
+        let mut rb = program();
+        rb.dec(CELLS).add(CELLS, CELLS + 1).sub(CELLS, CELLS + 1);
+
+        // The first screen page is left free while the screen register is unset
+        assert_eq!(rb.get_table_addr(Table::Dec), Some(0x020000));
+        assert_eq!(rb.get_table_addr(Table::Add), Some(0x030000));
+        assert_eq!(rb.get_table_addr(Table::Sub), Some(0x040000));
+        assert!(rb.saved_len(PaddingPolicy::TrimZeros) <= 0x050000);
+        assert!(rb.overlaps().is_empty());
+    }
+
+    #[test]
+    fn test_failed_operations_leave_the_tables_untouched() {
+        let mut rb = program();
+        rb.org(MEMORY_SIZE - 9);
+        assert!(matches!(
+            rb.try_add16(CELLS, CELLS + 2),
+            Err(BytePusherError::AddressOutOfRange(_))
+        ));
+        assert!(matches!(
+            rb.try_dec(CELLS),
+            Err(BytePusherError::AddressOutOfRange(_))
+        ));
+        assert_eq!(rb.tables().count(), 0);
+        assert_eq!(rb.carry_page, None);
+    }
+
+    /// Builder whose program at 0x000400 overwrites a "data" section under the Deny policy
+    fn denied_program() -> RomBuilder {
+        let mut rb = RomBuilder::new();
+        rb.set_overlap_policy(OverlapPolicy::Deny);
+        rb.section("data", 0x000400).db_arr(&[0; 64]);
+        rb.section("program", 0x000400);
+        rb
+    }
+
+    #[test]
+    fn test_byte_operations_report_denied_overlaps() {
+        let mut rb = denied_program();
+        assert!(matches!(
+            rb.try_dec(CELLS),
+            Err(BytePusherError::Overlap(_))
+        ));
+        assert!(matches!(
+            rb.try_add_imm(CELLS, 1),
+            Err(BytePusherError::Overlap(_))
+        ));
+        assert!(matches!(
+            rb.try_add(CELLS, CELLS + 1),
+            Err(BytePusherError::Overlap(_))
+        ));
+        assert!(matches!(
+            rb.try_and(CELLS, CELLS + 1),
+            Err(BytePusherError::Overlap(_))
+        ));
+        assert!(matches!(
+            rb.try_not(CELLS),
+            Err(BytePusherError::Overlap(_))
+        ));
+    }
+
+    #[test]
+    fn test_wide_operations_report_denied_overlaps() {
+        let mut rb = denied_program();
+        assert!(matches!(
+            rb.try_inc16(CELLS),
+            Err(BytePusherError::Overlap(_))
+        ));
+        assert!(matches!(
+            rb.try_inc24(CELLS),
+            Err(BytePusherError::Overlap(_))
+        ));
+        assert!(matches!(
+            rb.try_dec16(CELLS),
+            Err(BytePusherError::Overlap(_))
+        ));
+        assert!(matches!(
+            rb.try_add16(CELLS, CELLS + 2),
+            Err(BytePusherError::Overlap(_))
+        ));
+    }
+}
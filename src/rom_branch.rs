@@ -0,0 +1,225 @@
+use crate::error::BytePusherError;
+use crate::rom_builder::{Addr, RomBuilder};
+use crate::rom_opcodes::INSTR_SIZE;
+use crate::tables::Table;
+
+/// Size of the code dispatching a selector byte through the jump tables
+pub(crate) const DISPATCH_SIZE: usize = 6 * INSTR_SIZE;
+
+// Conditional jumps: a selector byte picks the jump address from three 256-bytes
+// tables holding the high, middle and low bytes of each target. The selector is
+// copied into the source addresses of the table lookups, which in turn copy the
+// target bytes into the jump field of the final instruction.
+// Jump tables are placed after the existing content, 768 bytes for every branch.
+
+impl RomBuilder {
+    /// Jump to `targets[value]`, where value is the byte at the provided address
+    /// 63 bytes are written to the ROM
+    pub fn switch(&mut self, addr: usize, targets: &[Addr; 256]) -> &mut Self {
+        self.try_switch(addr, targets)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_switch(
+        &mut self,
+        addr: usize,
+        targets: &[Addr; 256],
+    ) -> Result<&mut Self, BytePusherError> {
+        let op = move |_: &Self| format!("switch 0x{:06X}", addr);
+        self.try_dispatch(op, INSTR_SIZE, targets, |rb, selector| {
+            rb.try_cpy(addr, selector).map(|_| ())
+        })
+    }
+
+    /// Jump to `target` if the byte at the provided address is zero
+    /// 63 bytes are written to the ROM
+    pub fn branch_if_zero(&mut self, addr: usize, target: impl Into<Addr>) -> &mut Self {
+        self.try_branch_if_zero(addr, target)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_branch_if_zero(
+        &mut self,
+        addr: usize,
+        target: impl Into<Addr>,
+    ) -> Result<&mut Self, BytePusherError> {
+        let target = target.into();
+        let op =
+            move |rb: &Self| format!("branch_if_zero 0x{:06X}, {}", addr, rb.format_addr(target));
+        let targets = self.branch_targets(INSTR_SIZE, 0, target);
+        self.try_dispatch(op, INSTR_SIZE, &targets, |rb, selector| {
+            rb.try_cpy(addr, selector).map(|_| ())
+        })
+    }
+
+    /// Jump to `target` if the bytes at addresses `a` and `b` are equal
+    /// 81 bytes are written to the ROM
+    pub fn branch_if_eq(&mut self, a: usize, b: usize, target: impl Into<Addr>) -> &mut Self {
+        self.try_branch_if_eq(a, b, target)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_branch_if_eq(
+        &mut self,
+        a: usize,
+        b: usize,
+        target: impl Into<Addr>,
+    ) -> Result<&mut Self, BytePusherError> {
+        let target = target.into();
+        let op = move |rb: &Self| {
+            format!(
+                "branch_if_eq 0x{:06X}, 0x{:06X}, {}",
+                a,
+                b,
+                rb.format_addr(target)
+            )
+        };
+        // a ^ b is zero only for equal bytes
+        self.check_space(3 * INSTR_SIZE + DISPATCH_SIZE)?;
+        let xor_table_addr = self.require_table(Table::Xor)?;
+        let targets = self.branch_targets(3 * INSTR_SIZE, 0, target);
+        self.try_dispatch(op, 3 * INSTR_SIZE, &targets, |rb, selector| {
+            rb.binary_lookup(xor_table_addr, a, b, selector).map(|_| ())
+        })
+    }
+
+    /// Jump to `target` if the byte at address `a` is lower than the byte at address `b` (unsigned)
+    /// 81 bytes are written to the ROM
+    pub fn branch_if_lt(&mut self, a: usize, b: usize, target: impl Into<Addr>) -> &mut Self {
+        self.try_branch_if_lt(a, b, target)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_branch_if_lt(
+        &mut self,
+        a: usize,
+        b: usize,
+        target: impl Into<Addr>,
+    ) -> Result<&mut Self, BytePusherError> {
+        let target = target.into();
+        let op = move |rb: &Self| {
+            format!(
+                "branch_if_lt 0x{:06X}, 0x{:06X}, {}",
+                a,
+                b,
+                rb.format_addr(target)
+            )
+        };
+        self.check_space(3 * INSTR_SIZE + DISPATCH_SIZE)?;
+        let lt_table_addr = self.require_table(Table::Lt)?;
+        let targets = self.branch_targets(3 * INSTR_SIZE, 1, target);
+        self.try_dispatch(op, 3 * INSTR_SIZE, &targets, |rb, selector| {
+            rb.binary_lookup(lt_table_addr, a, b, selector).map(|_| ())
+        })
+    }
+
+    /// Targets jumping to `target` for the `taken` selector value
+    /// and after the branch for every other value
+    pub(crate) fn branch_targets(
+        &self,
+        load_size: usize,
+        taken: usize,
+        target: Addr,
+    ) -> [Addr; 256] {
+        let next = self.get_current_addr() + load_size + DISPATCH_SIZE;
+        let mut targets = [Addr::Abs(next); 256];
+        targets[taken] = target;
+        targets
+    }
+
+    /// Emit `load`, which copies the selector byte to the given address in `load_size` bytes,
+    /// followed by the dispatch through newly placed jump tables
+    /// The tables are written once the code is emitted
+    pub(crate) fn try_dispatch(
+        &mut self,
+        op: impl FnOnce(&Self) -> String,
+        load_size: usize,
+        targets: &[Addr; 256],
+        load: impl FnOnce(&mut Self, usize) -> Result<(), BytePusherError>,
+    ) -> Result<&mut Self, BytePusherError> {
+        self.check_space(load_size + DISPATCH_SIZE)?;
+
+        let tables = self.alloc_above(3 * 256, 256)?;
+        let current = self.get_current_addr();
+        let lookup = current + load_size + 2 * INSTR_SIZE;
+        let jump = lookup + 3 * INSTR_SIZE;
+        self.emit(op, |rb| -> Result<(), BytePusherError> {
+            load(rb, lookup + 2)?;
+            rb.try_cpy(lookup + 2, lookup + INSTR_SIZE + 2)?
+                .try_cpy(lookup + 2, lookup + 2 * INSTR_SIZE + 2)?;
+            for (i, table) in [tables, tables + 256, tables + 512].into_iter().enumerate() {
+                rb.try_cpy(table, jump + 6 + i)?;
+            }
+            // The jump address is written by the table lookups
+            rb.try_jmp(0x000000usize)?;
+            Ok(())
+        })?;
+
+        let next = self.get_current_addr();
+        self.with_owner("jump_table", |rb| -> Result<(), BytePusherError> {
+            rb.org(tables);
+            for shift in [16, 8, 0] {
+                for &target in targets {
+                    rb.try_write_addr_byte(target, shift)?;
+                }
+            }
+            Ok(())
+        })?;
+        Ok(self.org(next))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::BytePusherError;
+    use crate::regions::OverlapPolicy;
+    use crate::rom_builder::{Addr, RomBuilder};
+    use crate::vm::testing::{program, run};
+
+    #[test]
+    fn test_branches_in_vm() {
+        let mut rb = program();
+        let (selector, zero, one, three, five) = (0x80, 0x81, 0x82, 0x83, 0x84);
+        let results = 0x90;
+        rb.with_owner("data", |rb| {
+            rb.org(selector).db_arr(&[2, 0, 1, 3, 5]);
+        });
+
+        let fail = rb.new_label("fail");
+        let labels: Vec<_> = (0..4).map(|_| rb.new_label("taken")).collect();
+        let mut targets = [Addr::from(fail); 256];
+        targets[2] = labels[0].into();
+
+        rb.org(0x000100).switch(selector, &targets);
+        rb.label(labels[0]).cpy(one, results);
+        rb.branch_if_zero(zero, labels[1]).jmp(fail);
+        rb.label(labels[1]).cpy(one, results + 1);
+        rb.branch_if_zero(one, fail);
+        rb.branch_if_eq(five, five, labels[2]).jmp(fail);
+        rb.label(labels[2]).cpy(one, results + 2);
+        rb.branch_if_eq(five, three, fail);
+        rb.branch_if_lt(three, five, labels[3]).jmp(fail);
+        rb.label(labels[3]).cpy(one, results + 3);
+        rb.branch_if_lt(five, three, fail)
+            .branch_if_lt(five, five, fail);
+        rb.cpy(one, results + 4).label(fail);
+
+        let memory = run(&mut rb);
+        assert_eq!(memory[results..results + 5], [1; 5]);
+    }
+
+    #[test]
+    fn test_denied_branch_leaves_no_jump_tables() {
+        let mut rb = RomBuilder::new();
+        rb.set_overlap_policy(OverlapPolicy::Deny);
+        rb.org(0x000100).install_id_table();
+        rb.section("data", 0x000400).db_arr(&[0; 64]);
+
+        rb.section("program", 0x000400);
+        assert!(matches!(
+            rb.try_branch_if_zero(0x000080, 0x000000usize),
+            Err(BytePusherError::Overlap(_))
+        ));
+        assert!(rb.written_regions().all(|(_, owner)| owner != "jump_table"));
+    }
+}
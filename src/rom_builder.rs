@@ -1,41 +1,309 @@
+use std::collections::{BTreeMap, HashMap};
 use std::fs::File;
-use std::io::Write;
+use std::io::{BufWriter, Write};
 use std::ops::Range;
 
+use crate::banks::{BANK_SIZE, BankStore};
+use crate::disasm::find_table;
+use crate::error::BytePusherError;
+use crate::regions::{Overlap, OverlapPolicy, RegionMap};
+use crate::save::{PaddingPolicy, SaveOptions};
+use crate::tables::Table;
+
 pub const KEYBOARD_REGISTER_ADDR: usize = 0x000000;
 pub const PROGRAM_COUNTER_ADDR: usize = 0x000002;
 pub const SCREEN_REGISTER_ADDR: usize = 0x000005;
 pub const AUDIO_REGISTER_ADDR: usize = 0x000006;
 
+pub const MEMORY_SIZE: usize = 16 * 1024 * 1024;
+
+/// Size of the header holding the keyboard, program counter, screen and audio registers
+pub const HEADER_SIZE: usize = 8;
+
+/// Screen pages are 64 KiB aligned
+pub const SCREEN_PAGE_SIZE: usize = 65536;
+
+/// Audio pages are 256-bytes aligned
+pub const AUDIO_PAGE_SIZE: usize = 256;
+
+/// Symbolic ROM address, created with `new_label` and bound with `label`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Label(usize);
+
+/// Address operand: either a concrete address or a label plus an offset
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Addr {
+    Abs(usize),
+    Label(Label, usize),
+}
+
+impl From<usize> for Addr {
+    fn from(addr: usize) -> Self {
+        Addr::Abs(addr)
+    }
+}
+
+impl From<Label> for Addr {
+    fn from(label: Label) -> Self {
+        Addr::Label(label, 0)
+    }
+}
+
+impl std::ops::Add<usize> for Addr {
+    type Output = Addr;
+
+    fn add(self, rhs: usize) -> Self::Output {
+        match self {
+            Addr::Abs(addr) => Addr::Abs(addr + rhs),
+            Addr::Label(label, offset) => Addr::Label(label, offset + rhs),
+        }
+    }
+}
+
+impl std::ops::Add<usize> for Label {
+    type Output = Addr;
+
+    fn add(self, rhs: usize) -> Self::Output {
+        Addr::Label(self, rhs)
+    }
+}
+
+/// How a pending label reference has to be written once the label is bound
+#[derive(Debug, Clone, Copy)]
+enum FixupKind {
+    /// Full 24-bit address
+    Addr,
+    /// 24-bit address of the entry of a 256-bytes table selected by one byte of the label address
+    TableByte { table: usize, shift: u32 },
+    /// Single byte of the label address
+    Byte { shift: u32 },
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Fixup {
+    at: usize,
+    label: Label,
+    offset: usize,
+    kind: FixupKind,
+    /// Owner of the placeholder bytes
+    owner: usize,
+}
+
+/// Bytes produced by one opcode, or by consecutive raw writes when `op` is `None`
+#[derive(Debug, Clone)]
+struct Emission {
+    range: Range<usize>,
+    op: Option<String>,
+    owner: usize,
+}
+
+#[derive(Debug, Clone)]
+struct LabelInfo {
+    name: String,
+    addr: Option<usize>,
+    /// Created by an opcode for its own code, not listed nor exported as a symbol
+    internal: bool,
+}
+
+/// Register values stored in the first 8 bytes of a ROM, as written by `init_regs`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Header {
+    pub keyb_flags: u16,
+    pub program_addr: usize,
+    pub screen_addr: usize,
+    pub audio_addr: usize,
+}
+
+impl Header {
+    /// Decode the header registers, missing bytes are read as zero
+    pub fn parse(rom: &[u8]) -> Self {
+        let byte = |addr: usize| rom.get(addr).copied().unwrap_or(0) as usize;
+
+        Self {
+            keyb_flags: ((byte(KEYBOARD_REGISTER_ADDR) << 8) | byte(KEYBOARD_REGISTER_ADDR + 1))
+                as u16,
+            program_addr: (byte(PROGRAM_COUNTER_ADDR) << 16)
+                | (byte(PROGRAM_COUNTER_ADDR + 1) << 8)
+                | byte(PROGRAM_COUNTER_ADDR + 2),
+            screen_addr: byte(SCREEN_REGISTER_ADDR) << 16,
+            audio_addr: (byte(AUDIO_REGISTER_ADDR) << 16) | (byte(AUDIO_REGISTER_ADDR + 1) << 8),
+        }
+    }
+}
+
 pub struct RomBuilder {
-    rom: Vec<u8>,
+    rom: BankStore,
     program_counter: usize,
 
-    id_table_addr: Option<usize>,
-    inc_table_addr: Option<usize>,
+    tables: BTreeMap<Table, usize>,
+
+    labels: Vec<LabelInfo>,
+    fixups: Vec<Fixup>,
+
+    regions: RegionMap,
+    owner: usize,
+    overlaps: Vec<Overlap>,
+    overlap_policy: OverlapPolicy,
+
+    reserved: Vec<Range<usize>>,
+
+    emissions: Vec<Emission>,
+    emit_depth: usize,
+
+    loaded_owner: Option<usize>,
+
+    /// Return slot label of each routine, keyed by its entry label
+    pub(crate) return_slots: HashMap<Label, Label>,
+    pub(crate) current_routine: Option<Label>,
+    /// Base address of the software call stack, allocated on first use
+    pub(crate) call_stack: Option<usize>,
+    /// Target of `push_call` when the call stack is full
+    pub(crate) call_stack_overflow: Option<Addr>,
+    /// Scratch page of the multi-byte arithmetic, allocated on first use
+    pub(crate) carry_page: Option<usize>,
+    /// Counter of the `wait_frames` loops, allocated on first use
+    pub(crate) wait_counter: Option<usize>,
+}
+
+impl Default for RomBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl RomBuilder {
     pub fn new() -> Self {
+        let mut regions = RegionMap::new();
+        let owner = regions.owner_id("rom");
+
         Self {
-            rom: vec![0; 16 * 1024 * 1024],
+            rom: BankStore::new(),
             program_counter: 0,
-            id_table_addr: None,
-            inc_table_addr: None,
+            tables: BTreeMap::new(),
+            labels: Vec::new(),
+            fixups: Vec::new(),
+            regions,
+            owner,
+            overlaps: Vec::new(),
+            overlap_policy: OverlapPolicy::default(),
+            reserved: Vec::new(),
+            emissions: Vec::new(),
+            emit_depth: 0,
+            loaded_owner: None,
+            return_slots: HashMap::new(),
+            current_routine: None,
+            call_stack: None,
+            call_stack_overflow: None,
+            carry_page: None,
+            wait_counter: None,
         }
     }
 
-    pub fn save_to_file(&self, filename: &str) -> std::io::Result<()> {
-        let mut file = File::create(filename)?;
+    /// Start from the content of an existing ROM, to be patched with `org` and the usual opcodes
+    /// Identity and increment tables found in the ROM are reused, loaded bytes can be
+    /// overwritten without being reported as overlaps
+    /// Only non-zero bytes are marked as loaded, but the whole loaded length is kept
+    /// away from `alloc`, as zero bytes may belong to the loaded code or data
+    pub fn from_bytes(data: &[u8]) -> Result<Self, BytePusherError> {
+        if data.len() > MEMORY_SIZE {
+            return Err(BytePusherError::InvalidFormat);
+        }
 
-        let mut trimmed_rom = self.rom.clone();
-        while let Some(&0) = trimmed_rom.last() {
-            trimmed_rom.pop();
+        let mut rb = Self::new();
+        rb.rom.write(0, data);
+        let unary = [
+            Table::Id,
+            Table::Inc,
+            Table::Dec,
+            Table::Not,
+            Table::Shl,
+            Table::Shr,
+        ];
+        for table in unary {
+            if let Some(addr) = find_table(data, |x| table.entry(x as usize)) {
+                rb.tables.insert(table, addr);
+            }
+        }
+
+        let loaded = rb.regions.owner_id("loaded");
+        let mut start = 0;
+        while let Some(offset) = data[start..].iter().position(|&b| b != 0) {
+            start += offset;
+            let len = data[start..].iter().position(|&b| b == 0);
+            let end = len.map_or(data.len(), |len| start + len);
+            rb.regions.mark(start..end, loaded);
+            start = end;
+        }
+        rb.loaded_owner = Some(loaded);
+        if !data.is_empty() {
+            rb.reserved.push(0..data.len());
+        }
+        Ok(rb)
+    }
+
+    /// Load a `.BytePusher` file, see `from_bytes`
+    pub fn from_file(filename: &str) -> Result<Self, BytePusherError> {
+        Self::from_bytes(&std::fs::read(filename)?)
+    }
+
+    /// Register values currently stored in the header
+    pub fn header(&self) -> Header {
+        Header::parse(&self.rom[0..HEADER_SIZE])
+    }
+
+    /// Save the ROM without its trailing zero bytes, see `save_with_options`
+    pub fn save_to_file(&self, filename: &str) -> Result<(), BytePusherError> {
+        self.save_with_options(filename, &SaveOptions::default())
+    }
+
+    pub fn save_with_options(
+        &self,
+        filename: &str,
+        options: &SaveOptions,
+    ) -> Result<(), BytePusherError> {
+        self.finalize()?;
+
+        let mut file = BufWriter::new(File::create(filename)?);
+        self.rom
+            .write_to(&mut file, self.saved_len(options.padding))?;
+        file.flush()?;
+
+        if let Some(listing) = &options.listing {
+            self.listing().save(listing)?;
         }
-        file.write_all(&trimmed_rom)?;
         Ok(())
     }
 
+    /// Number of bytes written to the ROM file with the given policy
+    pub fn saved_len(&self, padding: PaddingPolicy) -> usize {
+        // Writes through slices and label fixups are not tracked as regions,
+        // the last non-zero byte covers them
+        let last_written = || {
+            self.written_regions()
+                .map(|(range, _)| range.end)
+                .max()
+                .unwrap_or(0)
+                .max(self.rom.used_len())
+        };
+
+        match padding {
+            PaddingPolicy::TrimZeros => self.rom.used_len(),
+            PaddingPolicy::Full => MEMORY_SIZE,
+            PaddingPolicy::LastWritten => last_written(),
+            PaddingPolicy::Align64K => last_written().next_multiple_of(BANK_SIZE),
+        }
+    }
+
+    /// ROM content as saved by `save_to_file`, without the trailing zeros
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.rom.read(0..self.rom.used_len())
+    }
+
+    /// Copy of the bytes in `range`
+    pub fn read(&self, range: Range<usize>) -> Vec<u8> {
+        self.rom.read(range)
+    }
+
     pub fn set_current_addr(&mut self, addr: usize) {
         self.program_counter = addr;
     }
@@ -52,8 +320,83 @@ impl RomBuilder {
         self.program_counter + 9
     }
 
-    pub fn write_addr(&mut self, addr: usize) -> &mut Self {
-        self.write_u24((addr & 0x00FFFFFF) as u32)
+    pub fn write_addr(&mut self, addr: impl Into<Addr>) -> &mut Self {
+        self.try_write_addr(addr)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_write_addr(&mut self, addr: impl Into<Addr>) -> Result<&mut Self, BytePusherError> {
+        let addr = addr.into();
+        match self.resolve(addr) {
+            Some(addr) => self.try_write_u24((addr & 0x00FFFFFF) as u32),
+            None => self.try_write_fixup(addr, FixupKind::Addr),
+        }
+    }
+
+    /// Write the 24-bit address of `table + ((value >> shift) & 0xFF)`,
+    /// deferring the computation if `value` refers to an unbound label
+    pub fn write_table_ref(
+        &mut self,
+        table: usize,
+        shift: u32,
+        value: impl Into<Addr>,
+    ) -> &mut Self {
+        self.try_write_table_ref(table, shift, value)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_write_table_ref(
+        &mut self,
+        table: usize,
+        shift: u32,
+        value: impl Into<Addr>,
+    ) -> Result<&mut Self, BytePusherError> {
+        let value = value.into();
+        match self.resolve(value) {
+            Some(value) => self.try_write_addr(table + ((value >> shift) & 0xFF)),
+            None => self.try_write_fixup(value, FixupKind::TableByte { table, shift }),
+        }
+    }
+
+    /// Write one byte of an address, `(addr >> shift) & 0xFF`,
+    /// deferring it if `addr` refers to an unbound label
+    pub fn write_addr_byte(&mut self, addr: impl Into<Addr>, shift: u32) -> &mut Self {
+        self.try_write_addr_byte(addr, shift)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_write_addr_byte(
+        &mut self,
+        addr: impl Into<Addr>,
+        shift: u32,
+    ) -> Result<&mut Self, BytePusherError> {
+        let addr = addr.into();
+        match self.resolve(addr) {
+            Some(addr) => self.try_write_u8((addr >> shift) as u8),
+            None => self.try_write_fixup(addr, FixupKind::Byte { shift }),
+        }
+    }
+
+    fn try_write_fixup(
+        &mut self,
+        addr: Addr,
+        kind: FixupKind,
+    ) -> Result<&mut Self, BytePusherError> {
+        let at = self.program_counter;
+        match kind {
+            FixupKind::Byte { .. } => self.try_write_u8(0)?,
+            _ => self.try_write_u24(0)?,
+        };
+        if let Addr::Label(label, offset) = addr {
+            self.fixups.push(Fixup {
+                at,
+                label,
+                offset,
+                kind,
+                owner: self.owner,
+            });
+        }
+        Ok(self)
     }
 
     pub fn write_current_addr(&mut self) -> &mut Self {
@@ -65,28 +408,404 @@ impl RomBuilder {
     }
 
     pub fn write_u24(&mut self, value: u32) -> &mut Self {
-        let addr = self.program_counter;
+        self.try_write_u24(value)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_write_u24(&mut self, value: u32) -> Result<&mut Self, BytePusherError> {
+        self.check_space(3)?;
+        self.track_write(3)?;
+        self.put_u24(self.program_counter, value);
+        self.program_counter += 3;
+        Ok(self)
+    }
+
+    fn put_u24(&mut self, addr: usize, value: u32) {
         self.rom[addr] = ((value & 0xFF0000) >> 16) as u8;
         self.rom[addr + 1] = ((value & 0xFF00) >> 8) as u8;
         self.rom[addr + 2] = (value & 0xFF) as u8;
-        self.program_counter += 3;
-        self
     }
 
     pub fn write_u16(&mut self, value: u16) -> &mut Self {
+        self.try_write_u16(value)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_write_u16(&mut self, value: u16) -> Result<&mut Self, BytePusherError> {
+        self.check_space(2)?;
+        self.track_write(2)?;
         let addr = self.program_counter;
         self.rom[addr] = ((value & 0xFF00) >> 8) as u8;
         self.rom[addr + 1] = (value & 0xFF) as u8;
         self.program_counter += 2;
-        self
+        Ok(self)
     }
 
     pub fn write_u8(&mut self, value: u8) -> &mut Self {
+        self.try_write_u8(value).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_write_u8(&mut self, value: u8) -> Result<&mut Self, BytePusherError> {
+        self.check_space(1)?;
+        self.track_write(1)?;
         self.rom[self.program_counter] = value;
         self.program_counter += 1;
+        Ok(self)
+    }
+
+    /// Check that `len` bytes can be written at the current address
+    pub fn check_space(&self, len: usize) -> Result<(), BytePusherError> {
+        if self.program_counter + len > MEMORY_SIZE {
+            return Err(BytePusherError::AddressOutOfRange(self.program_counter));
+        }
+        Ok(())
+    }
+
+    /// Set the owner tag of the following writes, used to report overlapping writes
+    pub fn owner(&mut self, name: &str) -> &mut Self {
+        self.owner = self.regions.owner_id(name);
         self
     }
 
+    pub fn get_owner(&self) -> &str {
+        self.regions.owner_name(self.owner)
+    }
+
+    /// Run `f` with a temporary owner tag
+    pub fn with_owner<T>(&mut self, name: &str, f: impl FnOnce(&mut Self) -> T) -> T {
+        let owner = self.owner;
+        self.owner = self.regions.owner_id(name);
+        let result = f(self);
+        self.owner = owner;
+        result
+    }
+
+    pub fn set_overlap_policy(&mut self, policy: OverlapPolicy) -> &mut Self {
+        self.overlap_policy = policy;
+        self
+    }
+
+    /// Writes that hit bytes already written by a previous write
+    pub fn overlaps(&self) -> &[Overlap] {
+        &self.overlaps
+    }
+
+    /// Written ranges with their owner tag, in address order
+    pub fn written_regions(&self) -> impl Iterator<Item = (Range<usize>, &str)> {
+        self.regions.iter()
+    }
+
+    /// Run `f` attributing the bytes it writes to the opcode described by `op` in the listing
+    /// Opcodes used by `f` are not recorded on their own, and `op` is only called for
+    /// recorded opcodes
+    pub fn emit<T>(
+        &mut self,
+        op: impl FnOnce(&Self) -> String,
+        f: impl FnOnce(&mut Self) -> T,
+    ) -> T {
+        let start = self.program_counter;
+        self.emit_depth += 1;
+        let result = f(self);
+        self.emit_depth -= 1;
+
+        if self.emit_depth == 0 && self.program_counter > start {
+            self.emissions.push(Emission {
+                range: start..self.program_counter,
+                op: Some(op(self)),
+                owner: self.owner,
+            });
+        }
+        result
+    }
+
+    /// Written ranges with the opcode that produced them (`None` for raw data)
+    /// and their owner tag, in emission order
+    pub fn emissions(&self) -> impl Iterator<Item = (Range<usize>, Option<&str>, &str)> {
+        self.emissions.iter().map(|emission| {
+            (
+                emission.range.clone(),
+                emission.op.as_deref(),
+                self.regions.owner_name(emission.owner),
+            )
+        })
+    }
+
+    /// Text of an address operand, labels are shown by name
+    pub fn format_addr(&self, addr: impl Into<Addr>) -> String {
+        match addr.into() {
+            Addr::Abs(addr) => format!("0x{:06X}", addr),
+            Addr::Label(label, 0) => self.get_label_name(label).to_string(),
+            Addr::Label(label, offset) => format!("{}+{}", self.get_label_name(label), offset),
+        }
+    }
+
+    /// Reserve `size` free bytes aligned to `alignment`, returning the start address
+    /// The lowest free address that fits is used, the header is never handed out
+    pub fn alloc(&mut self, size: usize, alignment: usize) -> Result<usize, BytePusherError> {
+        let used = self.used_ranges();
+
+        let mut candidate = HEADER_SIZE.next_multiple_of(alignment);
+        for range in used {
+            if candidate + size <= range.start {
+                break;
+            }
+            candidate = candidate.max(range.end.next_multiple_of(alignment));
+        }
+
+        if size == 0 || candidate + size > MEMORY_SIZE {
+            return Err(BytePusherError::OutOfMemory { size, alignment });
+        }
+
+        self.reserved.push(candidate..candidate + size);
+        Ok(candidate)
+    }
+
+    /// Reserve `size` free bytes aligned to `alignment`, using the highest address that fits
+    /// Used for scratch bytes that are not written to the ROM, away from hand-made layouts
+    pub fn alloc_top(&mut self, size: usize, alignment: usize) -> Result<usize, BytePusherError> {
+        let used = self.used_ranges();
+
+        let mut end = MEMORY_SIZE;
+        for range in used.iter().rev().chain([&(0..HEADER_SIZE)]) {
+            if size > 0 && end >= size {
+                let candidate = (end - size) / alignment * alignment;
+                if candidate >= range.end {
+                    self.reserved.push(candidate..candidate + size);
+                    return Ok(candidate);
+                }
+            }
+            end = end.min(range.start);
+        }
+
+        Err(BytePusherError::OutOfMemory { size, alignment })
+    }
+
+    /// Reserve `size` free bytes aligned to `alignment`, at the lowest address that fits
+    /// above every written byte and the 64 KiB bank of the current address
+    /// Used for tables placed while the code is being emitted, so that they follow
+    /// the existing content and leave room for the code being written
+    /// The screen and audio pages of the header are skipped, see `display_pages`
+    pub fn alloc_above(&mut self, size: usize, alignment: usize) -> Result<usize, BytePusherError> {
+        let floor = self
+            .written_regions()
+            .map(|(range, _)| range.end)
+            .chain([
+                HEADER_SIZE,
+                self.program_counter.next_multiple_of(BANK_SIZE),
+            ])
+            .max()
+            .unwrap_or(HEADER_SIZE);
+
+        let mut used = self.used_ranges();
+        used.extend(self.display_pages());
+        used.sort_by_key(|range| range.start);
+
+        let mut candidate = floor.next_multiple_of(alignment);
+        for range in used {
+            if candidate + size <= range.start {
+                break;
+            }
+            candidate = candidate.max(range.end.next_multiple_of(alignment));
+        }
+
+        if size == 0 || candidate + size > MEMORY_SIZE {
+            return Err(BytePusherError::OutOfMemory { size, alignment });
+        }
+
+        self.reserved.push(candidate..candidate + size);
+        Ok(candidate)
+    }
+
+    /// Screen and audio pages the header points to, which are usually written after
+    /// the code, while the screen register is unset the first screen page is assumed
+    fn display_pages(&self) -> [Range<usize>; 2] {
+        let header = self.header();
+        let screen = match header.screen_addr {
+            0 => SCREEN_PAGE_SIZE,
+            addr => addr,
+        };
+        [
+            screen..screen + SCREEN_PAGE_SIZE,
+            header.audio_addr..header.audio_addr + AUDIO_PAGE_SIZE,
+        ]
+    }
+
+    /// Written and reserved ranges sorted by address, overlapping ones are merged
+    fn used_ranges(&self) -> Vec<Range<usize>> {
+        let mut ranges: Vec<Range<usize>> = self
+            .written_regions()
+            .map(|(range, _)| range)
+            .chain(self.reserved.iter().cloned())
+            .collect();
+        ranges.sort_by_key(|range| range.start);
+
+        let mut used: Vec<Range<usize>> = Vec::with_capacity(ranges.len());
+        for range in ranges {
+            match used.last_mut() {
+                Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+                _ => used.push(range),
+            }
+        }
+        used
+    }
+
+    /// Reserve 256 bytes of audio samples
+    pub fn alloc_audio_page(&mut self) -> Result<usize, BytePusherError> {
+        self.alloc(AUDIO_PAGE_SIZE, AUDIO_PAGE_SIZE)
+    }
+
+    /// Reserve a 64 KiB screen page
+    pub fn alloc_screen_page(&mut self) -> Result<usize, BytePusherError> {
+        self.alloc_screen_pages(1)
+    }
+
+    /// Reserve `count` consecutive screen pages, so that the screen register
+    /// can step through them
+    pub fn alloc_screen_pages(&mut self, count: usize) -> Result<usize, BytePusherError> {
+        self.alloc(count * SCREEN_PAGE_SIZE, SCREEN_PAGE_SIZE)
+    }
+
+    /// Reserve `size` bytes of program space
+    pub fn alloc_program(&mut self, size: usize) -> Result<usize, BytePusherError> {
+        self.alloc(size, 1)
+    }
+
+    /// Emit the code written by `f` at the start of the largest free range, which `f`
+    /// receives, then reserve the bytes it used
+    /// Fails if the code runs into a written or reserved range
+    pub fn alloc_program_with(
+        &mut self,
+        f: impl FnOnce(&mut Self, usize) -> Result<(), BytePusherError>,
+    ) -> Result<usize, BytePusherError> {
+        let mut free = Vec::new();
+        let mut addr = HEADER_SIZE;
+        for range in self.used_ranges() {
+            if addr < range.start {
+                free.push(addr..range.start);
+            }
+            addr = addr.max(range.end);
+        }
+        free.push(addr..MEMORY_SIZE);
+        let Range { start, end: limit } = free
+            .into_iter()
+            .rev()
+            .max_by_key(|range| range.len())
+            .unwrap_or(MEMORY_SIZE..MEMORY_SIZE);
+
+        let current = self.get_current_addr();
+        self.org(start);
+        let result = f(self, start);
+        let end = self.get_current_addr();
+        self.org(current);
+        result?;
+
+        if end > limit {
+            return Err(BytePusherError::OutOfMemory {
+                size: end - start,
+                alignment: 1,
+            });
+        }
+        self.reserved.push(start..end);
+        Ok(start)
+    }
+
+    fn track_write(&mut self, len: usize) -> Result<(), BytePusherError> {
+        let range = self.program_counter..self.program_counter + len;
+        self.track_range(range.clone(), self.owner, None)?;
+        if self.emit_depth == 0 {
+            self.track_data(range);
+        }
+        Ok(())
+    }
+
+    /// Mark `range` as written by `owner`, recording the overlaps with bytes of other
+    /// writes; bytes already owned by `patched` are being patched and not reported
+    fn track_range(
+        &mut self,
+        range: Range<usize>,
+        owner: usize,
+        patched: Option<usize>,
+    ) -> Result<(), BytePusherError> {
+        let mut claimed = Vec::new();
+        for (written, previous_owner) in self.regions.find_overlaps(range.clone()) {
+            if Some(previous_owner) == self.loaded_owner {
+                claimed.push(written);
+                continue;
+            }
+            if Some(previous_owner) == patched {
+                continue;
+            }
+
+            let overlap = Overlap {
+                owner: self.regions.owner_name(owner).to_string(),
+                previous_owner: self.regions.owner_name(previous_owner).to_string(),
+                range: written,
+            };
+
+            if self.overlap_policy == OverlapPolicy::Deny {
+                return Err(BytePusherError::Overlap(overlap));
+            }
+
+            match self.overlaps.last_mut() {
+                Some(last)
+                    if last.range.end == overlap.range.start
+                        && last.owner == overlap.owner
+                        && last.previous_owner == overlap.previous_owner =>
+                {
+                    last.range.end = overlap.range.end;
+                }
+                _ => {
+                    // Code relying on an overwritten table silently computes garbage
+                    let table = overlap.previous_owner.ends_with("_table");
+                    if self.overlap_policy == OverlapPolicy::Warn || table {
+                        eprintln!("Warning: {}", overlap);
+                    }
+                    self.overlaps.push(overlap);
+                }
+            }
+        }
+
+        // Loaded bytes belong to whoever overwrites them
+        for written in claimed {
+            self.regions.assign(written, owner);
+        }
+        self.regions.mark(range, owner);
+        Ok(())
+    }
+
+    fn track_data(&mut self, range: Range<usize>) {
+        match self.emissions.last_mut() {
+            Some(last)
+                if last.op.is_none()
+                    && last.owner == self.owner
+                    && last.range.end == range.start =>
+            {
+                last.range.end = range.end;
+            }
+            _ => self.emissions.push(Emission {
+                range,
+                op: None,
+                owner: self.owner,
+            }),
+        }
+    }
+
+    /// Check that the current address is a multiple of `alignment`
+    pub fn check_alignment(
+        &self,
+        what: &'static str,
+        alignment: usize,
+    ) -> Result<(), BytePusherError> {
+        if !self.program_counter.is_multiple_of(alignment) {
+            return Err(BytePusherError::Misaligned {
+                what,
+                addr: self.program_counter,
+                alignment,
+            });
+        }
+        Ok(())
+    }
+
     pub fn init_regs(
         &mut self,
         keyb_flags: u16,
@@ -94,56 +813,211 @@ impl RomBuilder {
         screen_addr: usize,
         audio_addr: usize,
     ) -> &mut Self {
-        self.write_u16(keyb_flags)
-            .write_u24(program_addr as u32)
-            .write_u8((screen_addr >> 16) as u8)
-            .write_u16((audio_addr >> 8) as u16)
+        let op = move |_: &Self| {
+            format!(
+                "init_regs 0x{:04X}, 0x{:06X}, 0x{:06X}, 0x{:06X}",
+                keyb_flags, program_addr, screen_addr, audio_addr
+            )
+        };
+        self.with_owner("registers", |rb| {
+            rb.emit(op, |rb| {
+                rb.write_u16(keyb_flags)
+                    .write_u24(program_addr as u32)
+                    .write_u8((screen_addr >> 16) as u8)
+                    .write_u16((audio_addr >> 8) as u16);
+            });
+        });
+        self
     }
 
     pub fn install_id_table(&mut self) -> &mut Self {
-        if self.id_table_addr.is_some() {
-            panic!("Inc table already installed.")
-        }
+        self.install_table(Table::Id)
+    }
 
-        if self.get_current_addr() % 256 != 0 {
-            panic!("Id table needs to be 256-bytes memory aligned.")
-        }
+    pub fn try_install_id_table(&mut self) -> Result<&mut Self, BytePusherError> {
+        self.try_install_table(Table::Id)
+    }
+
+    pub fn get_id_table_addr(&self) -> Option<usize> {
+        self.get_table_addr(Table::Id)
+    }
+
+    pub fn install_inc_table(&mut self) -> &mut Self {
+        self.install_table(Table::Inc)
+    }
+
+    pub fn try_install_inc_table(&mut self) -> Result<&mut Self, BytePusherError> {
+        self.try_install_table(Table::Inc)
+    }
 
-        self.id_table_addr = Some(self.get_current_addr());
-        for x in 0..256 {
-            self.write_u8(x as u8);
+    pub fn get_inc_table_addr(&self) -> Option<usize> {
+        self.get_table_addr(Table::Inc)
+    }
+
+    /// Write a lookup table at the current address, which must be aligned to the table size
+    /// 256 bytes (unary tables) or 65536 bytes (binary tables) are written to the ROM
+    pub fn install_table(&mut self, table: Table) -> &mut Self {
+        self.try_install_table(table)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_install_table(&mut self, table: Table) -> Result<&mut Self, BytePusherError> {
+        if self.tables.contains_key(&table) {
+            return Err(BytePusherError::TableAlreadyInstalled(table));
         }
+        self.check_alignment(table.title(), table.size())?;
+        self.check_space(table.size())?;
 
-        self
+        let addr = self.get_current_addr();
+        self.with_owner(table.owner(), |rb| {
+            rb.emit(
+                |_| format!("install_{}", table.owner()),
+                |rb| {
+                    for index in 0..table.size() {
+                        rb.try_write_u8(table.entry(index))?;
+                    }
+                    Ok::<(), BytePusherError>(())
+                },
+            )
+        })?;
+        self.tables.insert(table, addr);
+
+        Ok(self)
     }
 
-    pub fn get_id_table_addr(&self) -> Option<usize> {
-        self.id_table_addr
+    pub fn get_table_addr(&self, table: Table) -> Option<usize> {
+        self.tables.get(&table).copied()
     }
 
-    pub fn install_inc_table(&mut self) -> &mut Self {
-        if self.inc_table_addr.is_some() {
-            panic!("Inc table already installed.")
+    /// Installed tables with their address
+    pub fn tables(&self) -> impl Iterator<Item = (Table, usize)> {
+        self.tables.iter().map(|(&table, &addr)| (table, addr))
+    }
+
+    /// Address of the table, installing it above the existing content on first use
+    /// The current address is left unchanged
+    /// The table skips the screen and audio pages of the header, or the first screen page
+    /// while the header is unset, but any other page written later can still overwrite it:
+    /// allocate the pages up front, overwriting a table prints a warning on stderr
+    pub fn require_table(&mut self, table: Table) -> Result<usize, BytePusherError> {
+        if let Some(addr) = self.get_table_addr(table) {
+            return Ok(addr);
         }
 
-        if self.get_current_addr() % 256 != 0 {
-            panic!("Inc table needs to be 256-bytes memory aligned.")
+        let addr = self.alloc_above(table.size(), table.size())?;
+        let current = self.get_current_addr();
+        self.org(addr);
+        let result = self.try_install_table(table).map(|_| ());
+        self.org(current);
+        result.map(|_| addr)
+    }
+
+    /// Create a new unbound label, it can be referenced before being bound
+    pub fn new_label(&mut self, name: &str) -> Label {
+        self.push_label(name, false)
+    }
+
+    /// Create a new unbound label for the code of an opcode, e.g. the resume point of a `sync`
+    /// It is left out of `labels()`, so listings and symbol files only show named labels
+    pub fn new_internal_label(&mut self, name: &str) -> Label {
+        self.push_label(name, true)
+    }
+
+    fn push_label(&mut self, name: &str, internal: bool) -> Label {
+        self.labels.push(LabelInfo {
+            name: name.to_string(),
+            addr: None,
+            internal,
+        });
+        Label(self.labels.len() - 1)
+    }
+
+    /// Bind the label to the current address and patch every pending reference to it
+    pub fn label(&mut self, label: Label) -> &mut Self {
+        let info = &mut self.labels[label.0];
+        if info.addr.is_some() {
+            panic!("Label '{}' already bound.", info.name)
         }
+        info.addr = Some(self.program_counter);
 
-        self.inc_table_addr = Some(self.get_current_addr());
-        for x in 0..256 {
-            self.write_u8(((x + 1) % 256) as u8);
+        let (resolved, pending): (Vec<Fixup>, Vec<Fixup>) = std::mem::take(&mut self.fixups)
+            .into_iter()
+            .partition(|f| f.label == label);
+        self.fixups = pending;
+        for fixup in resolved {
+            self.apply_fixup(fixup);
         }
 
         self
     }
 
-    pub fn get_inc_table_addr(&self) -> Option<usize> {
-        self.inc_table_addr
+    pub fn get_label_addr(&self, label: Label) -> Option<usize> {
+        self.labels[label.0].addr
+    }
+
+    pub fn get_label_name(&self, label: Label) -> &str {
+        &self.labels[label.0].name
+    }
+
+    /// Name and address of every label except the internal ones, in creation order
+    pub fn labels(&self) -> impl Iterator<Item = (&str, Option<usize>)> {
+        self.labels
+            .iter()
+            .filter(|info| !info.internal)
+            .map(|info| (info.name.as_str(), info.addr))
+    }
+
+    /// Concrete value of an address operand, `None` if it refers to an unbound label
+    pub fn resolve(&self, addr: impl Into<Addr>) -> Option<usize> {
+        match addr.into() {
+            Addr::Abs(addr) => Some(addr),
+            Addr::Label(label, offset) => self.get_label_addr(label).map(|addr| addr + offset),
+        }
+    }
+
+    fn apply_fixup(&mut self, fixup: Fixup) {
+        let len = match fixup.kind {
+            FixupKind::Byte { .. } => 1,
+            _ => 3,
+        };
+        // Placeholders can only have been overwritten by another owner under a lenient policy
+        self.track_range(fixup.at..fixup.at + len, fixup.owner, Some(fixup.owner))
+            .unwrap_or_else(|e| panic!("{}", e));
+
+        let value = self.labels[fixup.label.0].addr.unwrap_or_default() + fixup.offset;
+        let value = match fixup.kind {
+            FixupKind::Addr => value,
+            FixupKind::TableByte { table, shift } => table + ((value >> shift) & 0xFF),
+            FixupKind::Byte { shift } => {
+                self.rom[fixup.at] = (value >> shift) as u8;
+                return;
+            }
+        };
+        self.put_u24(fixup.at, (value & 0x00FFFFFF) as u32);
+    }
+
+    /// Check that every referenced label has been bound
+    pub fn finalize(&self) -> Result<(), BytePusherError> {
+        let mut unresolved: Vec<Label> = self.fixups.iter().map(|f| f.label).collect();
+        unresolved.sort_by_key(|label| label.0);
+        unresolved.dedup();
+
+        if unresolved.is_empty() {
+            Ok(())
+        } else {
+            Err(BytePusherError::UnresolvedLabels(
+                unresolved
+                    .into_iter()
+                    .map(|label| self.get_label_name(label).to_string())
+                    .collect(),
+            ))
+        }
     }
 }
 
 // Implement Index and IndexMut traits for RomBuilder at module scope
+// Mutable accesses are tracked as writes of the current owner, and panic
+// when the overlap policy denies them
 
 impl std::ops::Index<usize> for RomBuilder {
     type Output = u8;
@@ -155,6 +1029,9 @@ impl std::ops::Index<usize> for RomBuilder {
 
 impl std::ops::IndexMut<usize> for RomBuilder {
     fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        BankStore::check_bounds(&(index..index + 1));
+        self.track_range(index..index + 1, self.owner, None)
+            .unwrap_or_else(|e| panic!("{}", e));
         &mut self.rom[index]
     }
 }
@@ -169,6 +1046,148 @@ impl std::ops::Index<Range<usize>> for RomBuilder {
 
 impl std::ops::IndexMut<Range<usize>> for RomBuilder {
     fn index_mut(&mut self, index: Range<usize>) -> &mut Self::Output {
+        BankStore::check_bounds(&index);
+        if !index.is_empty() {
+            self.track_range(index.clone(), self.owner, None)
+                .unwrap_or_else(|e| panic!("{}", e));
+        }
         &mut self.rom[index]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_alloc_pages_and_exhaustion() {
+        let mut rb = RomBuilder::new();
+
+        assert_eq!(rb.alloc_screen_page().unwrap(), 0x010000);
+        assert_eq!(rb.alloc_audio_page().unwrap(), 0x000100);
+        assert_eq!(rb.alloc_program(10).unwrap(), 0x000008);
+        assert_eq!(rb.alloc_program(10).unwrap(), 0x000012);
+
+        rb.org(0x000200).db_arr(&[1; 0x100]);
+        assert_eq!(rb.alloc_audio_page().unwrap(), 0x000300);
+
+        assert_eq!(rb.alloc_screen_pages(254).unwrap(), 0x020000);
+        assert!(matches!(
+            rb.alloc_screen_page(),
+            Err(BytePusherError::OutOfMemory {
+                size: SCREEN_PAGE_SIZE,
+                alignment: SCREEN_PAGE_SIZE
+            })
+        ));
+
+        let mut rb = RomBuilder::new();
+        rb.org(0xFFFF80).db(0x01);
+        assert_eq!(rb.alloc_top(0x100, 0x100).unwrap(), 0xFFFE00);
+        assert_eq!(rb.alloc_top(0x10000, 0x10000).unwrap(), 0xFE0000);
+
+        let mut rb = RomBuilder::new();
+        assert_eq!(rb.alloc_screen_pages(255).unwrap(), 0x010000);
+        rb.org(0x020000).db(0x01);
+        assert_eq!(rb.alloc_top(0x100, 0x100).unwrap(), 0x00FF00);
+
+        let mut rb = RomBuilder::new();
+        assert_eq!(rb.alloc_screen_pages(255).unwrap(), 0x010000);
+        assert_eq!(rb.alloc_audio_page().unwrap(), 0x000100);
+        let start = rb
+            .alloc_program_with(|rb, start| {
+                rb.jmp(start);
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(start, 0x000200);
+        assert_eq!(rb.alloc_program(1).unwrap(), 0x000008);
+        assert_eq!(rb.alloc_program(0x100).unwrap(), 0x000209);
+        assert!(matches!(
+            rb.alloc_program_with(|rb, _| {
+                rb.db_arr(&[0; 0x10000]);
+                Ok(())
+            }),
+            Err(BytePusherError::OutOfMemory { size: 0x10000, .. })
+        ));
+
+        let mut rb = RomBuilder::new();
+        rb.org(0x000100).db(0x01);
+        assert_eq!(rb.alloc_above(0x100, 0x100).unwrap(), 0x020000);
+        rb.org(0x022345).db(0x01);
+        assert_eq!(rb.alloc_above(0x100, 0x100).unwrap(), 0x030000);
+        rb.org(0x000200);
+        assert_eq!(rb.alloc_above(0x10, 0x100).unwrap(), 0x022400);
+
+        // The pages of the header are skipped
+        let mut rb = RomBuilder::new();
+        rb.init_regs(0x0000, 0x000100, 0x020000, 0x030000);
+        assert_eq!(rb.alloc_above(0x100, 0x100).unwrap(), 0x010000);
+        assert_eq!(rb.alloc_above(0x10000, 0x10000).unwrap(), 0x040000);
+    }
+
+    #[test]
+    fn test_load_and_patch_rom() {
+        let mut original = RomBuilder::new();
+        original.init_regs(0x0000, 0x000300, 0x010000, 0x000200);
+        original.org(0x000100).install_id_table();
+        original.section("program", 0x000300).wait();
+        original.section("screen", 0x010000).db_arr(&[1; 16]);
+
+        let mut rb = RomBuilder::from_bytes(&original.to_bytes()).unwrap();
+        rb.set_overlap_policy(OverlapPolicy::Deny);
+        assert_eq!(
+            rb.header(),
+            Header {
+                keyb_flags: 0x0000,
+                program_addr: 0x000300,
+                screen_addr: 0x010000,
+                audio_addr: 0x000200
+            }
+        );
+        assert_eq!(rb.get_id_table_addr(), Some(0x000100));
+        assert_eq!(rb.get_inc_table_addr(), None);
+
+        rb.section("screen", rb.header().screen_addr)
+            .db_arr(&[2; 8]);
+        rb.section("program", 0x000300)
+            .cpyi(0x02, SCREEN_REGISTER_ADDR);
+        assert!(rb.overlaps().is_empty());
+        assert_eq!(rb[0x010000..0x010010], [[2; 8], [1; 8]].concat());
+        assert_eq!(rb[0x000300..0x000303], [0x00, 0x01, 0x02]);
+        // Zero bytes of the loaded ROM are not handed out
+        assert_eq!(rb.alloc_audio_page().unwrap(), 0x010100);
+
+        let regions: Vec<_> = rb.written_regions().collect();
+        assert!(regions.contains(&(0x010000..0x010008, "screen")));
+        assert!(regions.contains(&(0x010008..0x010010, "loaded")));
+        assert!(!regions.iter().any(|(range, _)| range.contains(&0x000100)));
+
+        let mut rb = RomBuilder::from_bytes(&[[0x12; 8], [0; 8], [0x34; 8]].concat()).unwrap();
+        assert_eq!(rb.alloc(8, 1).unwrap(), 0x000018);
+
+        let mut rom = RomBuilder::new();
+        rom.org(0x000100).nop();
+        let mut rb = RomBuilder::from_bytes(&rom.to_bytes()).unwrap();
+        assert_eq!(rb.alloc_program(0xFE).unwrap(), 0x000109);
+
+        assert!(matches!(
+            RomBuilder::from_bytes(&vec![0; MEMORY_SIZE + 1]),
+            Err(BytePusherError::InvalidFormat)
+        ));
+    }
+
+    #[test]
+    fn test_index_past_the_address_space_panics() {
+        let mut rb = RomBuilder::new();
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            rb[0x1000005] = 1;
+        }));
+        let message = result.unwrap_err().downcast::<String>().unwrap();
+        assert_eq!(
+            *message,
+            BytePusherError::AddressOutOfRange(0x1000005).to_string()
+        );
+        assert_eq!(rb.written_regions().count(), 0);
+        assert_eq!(rb.saved_len(PaddingPolicy::LastWritten), 0);
+    }
+}
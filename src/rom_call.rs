@@ -0,0 +1,305 @@
+use crate::error::BytePusherError;
+use crate::rom_branch::DISPATCH_SIZE;
+use crate::rom_builder::{Addr, Label, RomBuilder};
+use crate::rom_opcodes::INSTR_SIZE;
+use crate::tables::Table;
+
+/// Entries of the software call stack, the stack pointer wraps around
+pub const CALL_STACK_DEPTH: usize = 256;
+
+// Subroutines
+// `call` stores the return address into the jump field of the routine's `ret`
+// instruction, the return slot, so every routine can be active once at a time.
+// `push_call`/`pop_ret` keep return addresses on a software stack instead and
+// allow recursion: three 256-bytes pages hold the high, middle and low bytes of
+// the return addresses, followed by the stack pointer.
+
+impl RomBuilder {
+    /// Bind the entry label of a routine at the current address,
+    /// the following `ret` calls return from it until `end_routine` or the next `routine`
+    /// 0 bytes are written to the ROM
+    pub fn routine(&mut self, entry: Label) -> &mut Self {
+        self.current_routine = Some(entry);
+        self.label(entry)
+    }
+
+    /// End the body of the current routine, a later `ret` outside a routine is an error
+    /// 0 bytes are written to the ROM
+    pub fn end_routine(&mut self) -> &mut Self {
+        self.current_routine = None;
+        self
+    }
+
+    /// Label of the instruction holding the return address of the routine
+    fn return_slot(&mut self, entry: Label) -> Label {
+        if let Some(&slot) = self.return_slots.get(&entry) {
+            return slot;
+        }
+        let slot = self.new_label(&format!("{}.ret", self.get_label_name(entry)));
+        self.return_slots.insert(entry, slot);
+        slot
+    }
+
+    /// Call the routine starting at `entry`, execution continues after the call on `ret`
+    /// 36 bytes are written to the ROM
+    pub fn call(&mut self, entry: Label) -> &mut Self {
+        self.try_call(entry).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_call(&mut self, entry: Label) -> Result<&mut Self, BytePusherError> {
+        self.get_id_table_addr()
+            .ok_or(BytePusherError::TableMissing(Table::Id))?;
+        self.check_space(4 * INSTR_SIZE)?;
+
+        let slot = self.return_slot(entry);
+        let back = self.new_internal_label("call.return");
+        let op = move |rb: &Self| format!("call {}", rb.get_label_name(entry));
+        self.emit(op, |rb| -> Result<(), BytePusherError> {
+            rb.try_cpyi_addr(back, slot + 6)?.try_jmp(entry)?;
+            Ok(())
+        })?;
+        Ok(self.label(back))
+    }
+
+    /// Return from the current routine to the instruction following its `call`
+    /// The first `ret` of a routine is its return slot, the others jump to it
+    /// 9 bytes are written to the ROM
+    pub fn ret(&mut self) -> &mut Self {
+        self.try_ret().unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_ret(&mut self) -> Result<&mut Self, BytePusherError> {
+        let entry = self.current_routine.ok_or(BytePusherError::NotInRoutine)?;
+        self.check_space(INSTR_SIZE)?;
+
+        let slot = self.return_slot(entry);
+        let op = move |rb: &Self| format!("ret {}", rb.get_label_name(entry));
+        if self.get_label_addr(slot).is_some() {
+            self.emit(op, |rb| rb.try_jmp(slot).map(|_| ()))?;
+        } else {
+            // The jump address is written by `call`
+            let start = self.get_current_addr();
+            self.emit(op, |rb| rb.try_jmp(0x000000usize).map(|_| ()))?;
+            let end = self.get_current_addr();
+            self.org(start).label(slot).org(end);
+        }
+        Ok(self)
+    }
+
+    /// Base address of the call stack pages, the stack pointer follows them
+    fn require_call_stack(&mut self) -> Result<usize, BytePusherError> {
+        if let Some(stack) = self.call_stack {
+            return Ok(stack);
+        }
+        let stack = self.alloc_top(3 * CALL_STACK_DEPTH + 1, 256)?;
+        self.call_stack = Some(stack);
+        Ok(stack)
+    }
+
+    /// Jump to `handler` instead of pushing once the call stack holds 255 entries,
+    /// applies to the following `push_call`
+    /// 0 bytes are written to the ROM
+    pub fn on_call_stack_overflow(&mut self, handler: impl Into<Addr>) -> &mut Self {
+        self.call_stack_overflow = Some(handler.into());
+        self
+    }
+
+    /// Push the return address on the call stack and jump to `target`
+    /// Without `on_call_stack_overflow` the stack pointer wraps around and
+    /// the 257th nested call overwrites the oldest return address
+    /// 81 bytes are written to the ROM, 144 with the overflow check
+    pub fn push_call(&mut self, target: impl Into<Addr>) -> &mut Self {
+        self.try_push_call(target)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_push_call(&mut self, target: impl Into<Addr>) -> Result<&mut Self, BytePusherError> {
+        let id_table_addr = self
+            .get_id_table_addr()
+            .ok_or(BytePusherError::TableMissing(Table::Id))?;
+        let overflow = self.call_stack_overflow;
+        let check_size = if overflow.is_some() {
+            INSTR_SIZE + DISPATCH_SIZE
+        } else {
+            0
+        };
+        self.check_space(check_size + 9 * INSTR_SIZE)?;
+        let inc_table_addr = self.require_table(Table::Inc)?;
+        let stack = self.require_call_stack()?;
+
+        let target = target.into();
+        let sp = stack + 3 * CALL_STACK_DEPTH;
+        let back = self.new_internal_label("call.return");
+        let op = move |rb: &Self| format!("push_call {}", rb.format_addr(target));
+        self.emit(op, |rb| -> Result<(), BytePusherError> {
+            if let Some(handler) = overflow {
+                // Leave the last entry free: a full stack has its pointer back at zero
+                let targets = rb.branch_targets(INSTR_SIZE, CALL_STACK_DEPTH - 1, handler);
+                rb.try_dispatch(
+                    |_| String::new(),
+                    INSTR_SIZE,
+                    &targets,
+                    |rb, selector| rb.try_cpy(sp, selector).map(|_| ()),
+                )?;
+            }
+            // Point the targets of the three pushes to the top of the stack
+            let push = rb.get_current_addr() + 3 * INSTR_SIZE;
+            for i in 0..3 {
+                rb.try_cpy(sp, push + i * INSTR_SIZE + 5)?;
+            }
+            for (i, shift) in [16, 8, 0].into_iter().enumerate() {
+                let next = rb.get_next_instr_addr();
+                rb.try_write_table_ref(id_table_addr, shift, back)?
+                    .try_write_addr(stack + i * CALL_STACK_DEPTH)?
+                    .try_write_addr(next)?;
+            }
+            let lookup = rb.get_next_instr_addr() + 2;
+            rb.try_cpy(sp, lookup)?
+                .try_cpy(inc_table_addr, sp)?
+                .try_jmp(target)?;
+            Ok(())
+        })?;
+        Ok(self.label(back))
+    }
+
+    /// Pop a return address from the call stack and jump to it
+    /// 81 bytes are written to the ROM
+    pub fn pop_ret(&mut self) -> &mut Self {
+        self.try_pop_ret().unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_pop_ret(&mut self) -> Result<&mut Self, BytePusherError> {
+        self.check_space(9 * INSTR_SIZE)?;
+        let dec_table_addr = self.require_table(Table::Dec)?;
+        let stack = self.require_call_stack()?;
+
+        let sp = stack + 3 * CALL_STACK_DEPTH;
+        let lookup = self.get_current_addr() + 5 * INSTR_SIZE;
+        let jump = lookup + 3 * INSTR_SIZE;
+        self.emit(
+            |_| "pop_ret".to_string(),
+            |rb| -> Result<(), BytePusherError> {
+                let update = rb.get_next_instr_addr() + 2;
+                rb.try_cpy(sp, update)?.try_cpy(dec_table_addr, sp)?;
+                // Point the sources of the three lookups to the top of the stack
+                for i in 0..3 {
+                    rb.try_cpy(sp, lookup + i * INSTR_SIZE + 2)?;
+                }
+                for i in 0..3 {
+                    rb.try_cpy(stack + i * CALL_STACK_DEPTH, jump + 6 + i)?;
+                }
+                // The jump address is written by the lookups
+                rb.try_jmp(0x000000usize)?;
+                Ok(())
+            },
+        )?;
+        Ok(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CALL_STACK_DEPTH;
+    use crate::error::BytePusherError;
+    use crate::regions::OverlapPolicy;
+    use crate::rom_builder::RomBuilder;
+    use crate::vm::testing::{program, run};
+
+    #[test]
+    fn test_call_and_ret_in_vm() {
+        let mut rb = program();
+        let (counter, twice) = (0x000080, 0x000081);
+        rb.org(0x008000).install_id_table().org(0x000100);
+        assert!(matches!(rb.try_ret(), Err(BytePusherError::NotInRoutine)));
+
+        let increment = rb.new_label("increment");
+        let double = rb.new_label("double");
+        let end = rb.new_label("end");
+        rb.call(increment).call(double).call(increment).jmp(end);
+
+        rb.routine(increment).add_imm(counter, 1).ret();
+        rb.routine(double)
+            .add_imm(twice, 1)
+            .call(increment)
+            .add_imm(twice, 1)
+            .ret()
+            .end_routine();
+        assert!(matches!(rb.try_ret(), Err(BytePusherError::NotInRoutine)));
+        rb.label(end);
+
+        let memory = run(&mut rb);
+        assert_eq!(memory[counter..counter + 2], [3, 2]);
+    }
+
+    #[test]
+    fn test_recursive_calls_on_stack() {
+        let mut rb = program();
+        let (depth, counter, after) = (0x000080, 0x000081, 0x000082);
+        rb.with_owner("data", |rb| {
+            rb.org(depth).db(5);
+        });
+        rb.org(0x008000).install_id_table().org(0x000100);
+
+        let countdown = rb.new_label("countdown");
+        let done = rb.new_label("done");
+        let end = rb.new_label("end");
+        rb.push_call(countdown).add_imm(after, 1).jmp(end);
+
+        // Recurse until depth reaches zero, counting calls and returns
+        rb.label(countdown)
+            .branch_if_zero(depth, done)
+            .dec(depth)
+            .add_imm(counter, 1)
+            .push_call(countdown)
+            .add_imm(counter, 0x10);
+        rb.label(done).pop_ret();
+        rb.label(end);
+
+        let memory = run(&mut rb);
+        assert_eq!(memory[depth..depth + 3], [0, 0x55, 1]);
+    }
+
+    #[test]
+    fn test_call_stack_overflow() {
+        let mut rb = program();
+        let (counter, overflowed) = (0x000080, 0x000081);
+        rb.org(0x008000).install_id_table().org(0x000100);
+
+        let recurse = rb.new_label("recurse");
+        let overflow = rb.new_label("overflow");
+        let end = rb.new_label("end");
+        rb.on_call_stack_overflow(overflow).push_call(recurse);
+
+        // Recurse forever, the handler stops once 255 return addresses are pushed
+        rb.label(recurse).add_imm(counter, 1).push_call(recurse);
+        rb.label(overflow).cpyi(1, overflowed).jmp(end);
+        rb.label(end);
+
+        let memory = run(&mut rb);
+        assert_eq!(memory[counter..counter + 2], [255, 1]);
+        let sp = rb.call_stack.unwrap() + 3 * CALL_STACK_DEPTH;
+        assert_eq!(memory[sp], 255);
+    }
+
+    #[test]
+    fn test_calls_report_denied_overlaps() {
+        let mut rb = RomBuilder::new();
+        rb.set_overlap_policy(OverlapPolicy::Deny);
+        rb.org(0x000100).install_id_table();
+        rb.section("data", 0x000400).db_arr(&[0; 128]);
+
+        let routine = rb.new_label("routine");
+        rb.section("program", 0x000400);
+        assert!(matches!(
+            rb.try_call(routine),
+            Err(BytePusherError::Overlap(_))
+        ));
+        assert!(matches!(
+            rb.try_push_call(routine),
+            Err(BytePusherError::Overlap(_))
+        ));
+        assert!(matches!(rb.try_pop_ret(), Err(BytePusherError::Overlap(_))));
+        rb.routine(routine);
+        assert!(matches!(rb.try_ret(), Err(BytePusherError::Overlap(_))));
+    }
+}
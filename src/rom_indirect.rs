@@ -0,0 +1,155 @@
+use crate::error::BytePusherError;
+use crate::rom_builder::RomBuilder;
+use crate::rom_opcodes::INSTR_SIZE;
+
+// Indirect and indexed accesses, done by copying the pointer or the index into
+// the operand fields of the following instruction before it is executed.
+// Pointers are 3 bytes long, most significant byte first like every BytePusher address.
+
+impl RomBuilder {
+    /// Copy the byte pointed by the 24-bit pointer at `ptr` to `dst`
+    /// 36 bytes are written to the ROM
+    pub fn load_indirect(&mut self, ptr: usize, dst: usize) -> &mut Self {
+        self.try_load_indirect(ptr, dst)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_load_indirect(
+        &mut self,
+        ptr: usize,
+        dst: usize,
+    ) -> Result<&mut Self, BytePusherError> {
+        self.check_space(4 * INSTR_SIZE)?;
+
+        let access = self.get_current_addr() + 3 * INSTR_SIZE;
+        self.emit(
+            |_| format!("load_indirect 0x{:06X}, 0x{:06X}", ptr, dst),
+            |rb| -> Result<(), BytePusherError> {
+                for i in 0..3 {
+                    rb.try_cpy(ptr + i, access + i)?;
+                }
+                // The source address is written by the copies above
+                rb.try_cpy(0x000000, dst)?;
+                Ok(())
+            },
+        )?;
+        Ok(self)
+    }
+
+    /// Copy the byte at `src` to the address held by the 24-bit pointer at `ptr`
+    /// 36 bytes are written to the ROM
+    pub fn store_indirect(&mut self, src: usize, ptr: usize) -> &mut Self {
+        self.try_store_indirect(src, ptr)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_store_indirect(
+        &mut self,
+        src: usize,
+        ptr: usize,
+    ) -> Result<&mut Self, BytePusherError> {
+        self.check_space(4 * INSTR_SIZE)?;
+
+        let access = self.get_current_addr() + 3 * INSTR_SIZE;
+        self.emit(
+            |_| format!("store_indirect 0x{:06X}, 0x{:06X}", src, ptr),
+            |rb| -> Result<(), BytePusherError> {
+                for i in 0..3 {
+                    rb.try_cpy(ptr + i, access + 3 + i)?;
+                }
+                // The target address is written by the copies above
+                rb.try_cpy(src, 0x000000)?;
+                Ok(())
+            },
+        )?;
+        Ok(self)
+    }
+
+    /// Copy `base[index]` to `dst`, where index is the byte at the provided address
+    /// The base address must be 256-bytes aligned, so that the index is its low byte
+    /// 18 bytes are written to the ROM
+    pub fn cpy_indexed(&mut self, base: usize, index: usize, dst: usize) -> &mut Self {
+        self.try_cpy_indexed(base, index, dst)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_cpy_indexed(
+        &mut self,
+        base: usize,
+        index: usize,
+        dst: usize,
+    ) -> Result<&mut Self, BytePusherError> {
+        if !base.is_multiple_of(256) {
+            return Err(BytePusherError::Misaligned {
+                what: "Indexed base address",
+                addr: base,
+                alignment: 256,
+            });
+        }
+        self.check_space(2 * INSTR_SIZE)?;
+
+        let op =
+            move |_: &Self| format!("cpy_indexed 0x{:06X}, 0x{:06X}, 0x{:06X}", base, index, dst);
+        self.emit(op, |rb| -> Result<(), BytePusherError> {
+            let lookup = rb.get_next_instr_addr() + 2;
+            rb.try_cpy(index, lookup)?.try_cpy(base, dst)?;
+            Ok(())
+        })?;
+        Ok(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::BytePusherError;
+    use crate::regions::OverlapPolicy;
+    use crate::rom_builder::RomBuilder;
+    use crate::vm::testing::{program, run};
+
+    #[test]
+    fn test_indirect_access_in_vm() {
+        let mut rb = program();
+        let (ptr, index, value) = (0x000080, 0x000083, 0x000084);
+        let (loaded, indexed) = (0x000090, 0x000091);
+        let array = 0x000400;
+        rb.with_owner("data", |rb| {
+            rb.org(ptr).db_arr(&[0x00, 0x04, 0x03, 0x05, 0xAA]);
+            rb.org(array).db_arr(&[10, 11, 12, 13, 14, 15]);
+        });
+
+        rb.org(0x000100)
+            .load_indirect(ptr, loaded)
+            .store_indirect(value, ptr)
+            .cpy_indexed(array, index, indexed);
+
+        let memory = run(&mut rb);
+        assert_eq!(memory[loaded..loaded + 2], [13, 15]);
+        assert_eq!(memory[array..array + 6], [10, 11, 12, 0xAA, 14, 15]);
+
+        assert!(matches!(
+            rb.try_cpy_indexed(array + 1, index, indexed),
+            Err(BytePusherError::Misaligned { addr: 0x000401, .. })
+        ));
+    }
+
+    #[test]
+    fn test_indirect_access_reports_denied_overlaps() {
+        let mut rb = RomBuilder::new();
+        rb.set_overlap_policy(OverlapPolicy::Deny);
+        rb.section("data", 0x000400).db_arr(&[0; 64]);
+
+        rb.section("program", 0x000400);
+        assert!(matches!(
+            rb.try_load_indirect(0x000080, 0x000090),
+            Err(BytePusherError::Overlap(_))
+        ));
+        assert!(matches!(
+            rb.try_store_indirect(0x000090, 0x000080),
+            Err(BytePusherError::Overlap(_))
+        ));
+        assert!(matches!(
+            rb.try_cpy_indexed(0x000400, 0x000080, 0x000090),
+            Err(BytePusherError::Overlap(_))
+        ));
+    }
+}
@@ -0,0 +1,148 @@
+use crate::error::BytePusherError;
+use crate::rom_builder::RomBuilder;
+use crate::tables::Table;
+
+// Table-driven bitwise operations on bytes
+// Tables that are not installed yet are placed after the existing content on first use
+
+impl RomBuilder {
+    /// Bitwise AND of the byte at address `b` into the byte at address `a`
+    /// 27 bytes are written to the ROM
+    pub fn and(&mut self, a: usize, b: usize) -> &mut Self {
+        self.try_and(a, b).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_and(&mut self, a: usize, b: usize) -> Result<&mut Self, BytePusherError> {
+        self.try_binary_op("and", Table::And, a, b)
+    }
+
+    /// Bitwise OR of the byte at address `b` into the byte at address `a`
+    /// 27 bytes are written to the ROM
+    pub fn or(&mut self, a: usize, b: usize) -> &mut Self {
+        self.try_or(a, b).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_or(&mut self, a: usize, b: usize) -> Result<&mut Self, BytePusherError> {
+        self.try_binary_op("or", Table::Or, a, b)
+    }
+
+    /// Bitwise XOR of the byte at address `b` into the byte at address `a`
+    /// 27 bytes are written to the ROM
+    pub fn xor(&mut self, a: usize, b: usize) -> &mut Self {
+        self.try_xor(a, b).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_xor(&mut self, a: usize, b: usize) -> Result<&mut Self, BytePusherError> {
+        self.try_binary_op("xor", Table::Xor, a, b)
+    }
+
+    /// Mask the byte at the provided address with an immediate value,
+    /// e.g. to test keys in the keyboard register
+    /// 18 bytes are written to the ROM
+    pub fn and_imm(&mut self, addr: usize, mask: u8) -> &mut Self {
+        self.try_and_imm(addr, mask)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_and_imm(&mut self, addr: usize, mask: u8) -> Result<&mut Self, BytePusherError> {
+        self.try_binary_imm_op("and_imm", Table::And, addr, mask)
+    }
+
+    /// Set the bits of an immediate value in the byte at the provided address
+    /// 18 bytes are written to the ROM
+    pub fn or_imm(&mut self, addr: usize, bits: u8) -> &mut Self {
+        self.try_or_imm(addr, bits)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_or_imm(&mut self, addr: usize, bits: u8) -> Result<&mut Self, BytePusherError> {
+        self.try_binary_imm_op("or_imm", Table::Or, addr, bits)
+    }
+
+    /// Flip the bits of an immediate value in the byte at the provided address
+    /// 18 bytes are written to the ROM
+    pub fn xor_imm(&mut self, addr: usize, bits: u8) -> &mut Self {
+        self.try_xor_imm(addr, bits)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_xor_imm(&mut self, addr: usize, bits: u8) -> Result<&mut Self, BytePusherError> {
+        self.try_binary_imm_op("xor_imm", Table::Xor, addr, bits)
+    }
+
+    /// Invert every bit of the byte at the provided address
+    /// 18 bytes are written to the ROM
+    pub fn not(&mut self, addr: usize) -> &mut Self {
+        self.try_not(addr).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_not(&mut self, addr: usize) -> Result<&mut Self, BytePusherError> {
+        self.try_unary_op("not", Table::Not, addr)
+    }
+
+    /// Shift the byte at the provided address one bit to the left
+    /// 18 bytes are written to the ROM
+    pub fn shl(&mut self, addr: usize) -> &mut Self {
+        self.try_shl(addr).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_shl(&mut self, addr: usize) -> Result<&mut Self, BytePusherError> {
+        self.try_unary_op("shl", Table::Shl, addr)
+    }
+
+    /// Shift the byte at the provided address one bit to the right
+    /// 18 bytes are written to the ROM
+    pub fn shr(&mut self, addr: usize) -> &mut Self {
+        self.try_shr(addr).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_shr(&mut self, addr: usize) -> Result<&mut Self, BytePusherError> {
+        self.try_unary_op("shr", Table::Shr, addr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rom_builder::KEYBOARD_REGISTER_ADDR;
+    use crate::vm::Machine;
+    use crate::vm::testing::{program, run};
+
+    #[test]
+    fn test_logic_in_vm() {
+        let mut rb = program();
+        let cells = 0x000080;
+        rb.with_owner("data", |rb| {
+            rb.org(cells)
+                .db_arr(&[0b1100, 0b1010, 0b1100, 0b1100, 0x0F, 0x81, 0x81, 0xF0]);
+        });
+
+        rb.org(0x000100)
+            .and(cells, cells + 1)
+            .or(cells + 2, cells + 1)
+            .xor(cells + 3, cells + 1)
+            .not(cells + 4)
+            .shl(cells + 5)
+            .shr(cells + 6)
+            .xor_imm(cells + 7, 0xFF)
+            .or_imm(cells + 7, 0x80);
+
+        let memory = run(&mut rb);
+        assert_eq!(
+            memory[cells..cells + 8],
+            [0b1000, 0b1010, 0b1110, 0b0110, 0xF0, 0x02, 0x40, 0x8F]
+        );
+    }
+
+    #[test]
+    fn test_mask_keyboard_bits() {
+        let mut rb = program();
+        rb.cpy(KEYBOARD_REGISTER_ADDR + 1, 0x000080)
+            .and_imm(0x000080, 0x12)
+            .wait();
+
+        let mut machine = Machine::from_builder(&rb).unwrap();
+        machine.set_keys(0x0037);
+        machine.run_frame();
+        assert_eq!(machine.memory()[0x000080], 0x12);
+    }
+}
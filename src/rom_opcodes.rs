@@ -1,4 +1,65 @@
-use crate::rom_builder::{PROGRAM_COUNTER_ADDR, RomBuilder};
+use crate::error::BytePusherError;
+use crate::rom_builder::{Addr, PROGRAM_COUNTER_ADDR, RomBuilder};
+use crate::tables::Table;
+use crate::vm::INSTRUCTIONS_PER_FRAME;
+
+/// Size of a ByteByteJump instruction, every opcode is a multiple of it
+pub const INSTR_SIZE: usize = 9;
+
+/// Instructions executed by `sync`, the last one ends the frame
+const SYNC_INSTRUCTIONS: usize = 4;
+
+/// Size and speed trade-offs of `memcpy` and `memset`
+#[derive(Debug, Clone)]
+pub struct BlockOptions {
+    /// Longest block written by unrolled code (one instruction and 9 ROM bytes per byte),
+    /// longer blocks run whole 256-bytes pages through a loop when they are page aligned
+    pub unroll_limit: usize,
+    /// Instructions executed before the block is continued in the next frame with `sync`,
+    /// what is left of the frame is available to the code running before the block
+    pub frame_budget: usize,
+}
+
+impl Default for BlockOptions {
+    fn default() -> Self {
+        Self {
+            unroll_limit: 256,
+            frame_budget: INSTRUCTIONS_PER_FRAME - 4096,
+        }
+    }
+}
+
+impl BlockOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn unroll_limit(mut self, unroll_limit: usize) -> Self {
+        self.unroll_limit = unroll_limit;
+        self
+    }
+
+    pub fn frame_budget(mut self, frame_budget: usize) -> Self {
+        self.frame_budget = frame_budget;
+        self
+    }
+}
+
+/// Source of the bytes written by a block operation
+#[derive(Debug, Clone, Copy)]
+enum BlockSource {
+    /// Bytes following the given address
+    Copy(usize),
+    /// The same byte, usually an entry of the identity table
+    Fill(usize),
+}
+
+/// Parts of a block operation, in execution order
+enum BlockStep {
+    Sync,
+    Page(usize),
+    Byte(usize),
+}
 
 impl RomBuilder {
     /// Move current address to the specified address
@@ -8,16 +69,66 @@ impl RomBuilder {
         self
     }
 
+    /// Move current address to the specified address and tag the following
+    /// writes as part of the named section (e.g. "program", "screen/frame_003")
+    /// 0 bytes are written to the address
+    pub fn section(&mut self, name: &str, addr: usize) -> &mut Self {
+        self.org(addr).owner(name)
+    }
+
     /// Write a ByteByteJump instruction with source, target, and jump addresses
     /// 9 bytes are written to the ROM
-    pub fn bbj(&mut self, source: usize, target: usize, jump: usize) -> &mut Self {
-        self.write_addr(source).write_addr(target).write_addr(jump)
+    pub fn bbj(
+        &mut self,
+        source: impl Into<Addr>,
+        target: impl Into<Addr>,
+        jump: impl Into<Addr>,
+    ) -> &mut Self {
+        self.try_bbj(source, target, jump)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_bbj(
+        &mut self,
+        source: impl Into<Addr>,
+        target: impl Into<Addr>,
+        jump: impl Into<Addr>,
+    ) -> Result<&mut Self, BytePusherError> {
+        let (source, target, jump) = (source.into(), target.into(), jump.into());
+        self.check_space(9)?;
+
+        let op = move |rb: &Self| {
+            format!(
+                "bbj {}, {}, {}",
+                rb.format_addr(source),
+                rb.format_addr(target),
+                rb.format_addr(jump)
+            )
+        };
+        self.emit(op, |rb| -> Result<(), BytePusherError> {
+            rb.try_write_addr(source)?
+                .try_write_addr(target)?
+                .try_write_addr(jump)?;
+            Ok(())
+        })?;
+        Ok(self)
     }
 
     /// No operation
     /// 9 bytes are written to the ROM
     pub fn nop(&mut self) -> &mut Self {
-        self.bbj(0x000000, 0x000000, self.get_next_instr_addr())
+        self.try_nop().unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_nop(&mut self) -> Result<&mut Self, BytePusherError> {
+        self.emit(
+            |_| "nop".to_string(),
+            |rb| {
+                let next = rb.get_next_instr_addr();
+                rb.try_bbj(0x000000usize, 0x000000usize, next).map(|_| ())
+            },
+        )?;
+        Ok(self)
     }
 
     /// Wait until next frame
@@ -25,44 +136,131 @@ impl RomBuilder {
     /// Program counter needs to be programmed before calling this function
     /// 9 bytes are written to the ROM
     pub fn wait(&mut self) -> &mut Self {
-        self.bbj(0x000000, 0x000000, self.get_current_addr())
+        self.try_wait().unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_wait(&mut self) -> Result<&mut Self, BytePusherError> {
+        self.emit(
+            |_| "wait".to_string(),
+            |rb| {
+                let current = rb.get_current_addr();
+                rb.try_bbj(0x000000usize, 0x000000usize, current)
+                    .map(|_| ())
+            },
+        )?;
+        Ok(self)
     }
 
     /// Wait until next frame before continuing
     /// 36 bytes are written to the ROM (maybe optimizable to 27 bytes)
     pub fn sync(&mut self) -> &mut Self {
-        self.cpyi_addr(self.get_current_addr() + 36, PROGRAM_COUNTER_ADDR)
-            .wait()
+        self.try_sync().unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_sync(&mut self) -> Result<&mut Self, BytePusherError> {
+        self.get_id_table_addr()
+            .ok_or(BytePusherError::TableMissing(Table::Id))?;
+        self.check_space(4 * INSTR_SIZE)?;
+
+        let resume = self.new_internal_label("sync.resume");
+        self.emit(
+            |_| "sync".to_string(),
+            |rb| -> Result<(), BytePusherError> {
+                rb.try_cpyi_addr(resume, PROGRAM_COUNTER_ADDR)?.try_wait()?;
+                Ok(())
+            },
+        )?;
+        Ok(self.label(resume))
     }
 
     /// Unconditional jump to provided address
     /// 9 bytes are written to the ROM
-    pub fn jmp(&mut self, addr: usize) -> &mut Self {
-        self.bbj(0x0, 0x0, addr)
+    pub fn jmp(&mut self, addr: impl Into<Addr>) -> &mut Self {
+        self.try_jmp(addr).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_jmp(&mut self, addr: impl Into<Addr>) -> Result<&mut Self, BytePusherError> {
+        let addr = addr.into();
+        let op = move |rb: &Self| format!("jmp {}", rb.format_addr(addr));
+        self.emit(op, |rb| rb.try_bbj(0x0usize, 0x0usize, addr).map(|_| ()))?;
+        Ok(self)
     }
 
     /// Copy byte value from source to target address
     /// 9 bytes are written to the ROM
     pub fn cpy(&mut self, source: usize, target: usize) -> &mut Self {
-        self.bbj(source, target, self.get_next_instr_addr())
+        self.try_cpy(source, target)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_cpy(&mut self, source: usize, target: usize) -> Result<&mut Self, BytePusherError> {
+        self.check_space(9)?;
+
+        let op = move |_: &Self| format!("cpy 0x{:06X}, 0x{:06X}", source, target);
+        self.emit(op, |rb| {
+            let next = rb.get_next_instr_addr();
+            rb.try_bbj(source, target, next).map(|_| ())
+        })?;
+        Ok(self)
     }
 
     /// Copy an immediate value to target address
     /// 9 bytes are written to the ROM
     pub fn cpyi(&mut self, value: u8, target: usize) -> &mut Self {
+        self.try_cpyi(value, target)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_cpyi(&mut self, value: u8, target: usize) -> Result<&mut Self, BytePusherError> {
         let id_table_addr = self
             .get_id_table_addr()
-            .expect("Please install the identity table first using install_id_table()");
+            .ok_or(BytePusherError::TableMissing(Table::Id))?;
+        self.check_space(9)?;
 
-        self.cpy(id_table_addr + value as usize, target)
+        let op = move |_: &Self| format!("cpyi 0x{:02X}, 0x{:06X}", value, target);
+        self.emit(op, |rb| {
+            rb.try_cpy(id_table_addr + value as usize, target)
+                .map(|_| ())
+        })?;
+        Ok(self)
     }
 
     /// Copy an immediate 24-bit value to target address
+    /// The value can be a label that is bound later
     /// 27 bytes are written to the ROM
-    pub fn cpyi_addr(&mut self, value: usize, target: usize) -> &mut Self {
-        self.cpyi(((value >> 16) & 0xFF) as u8, target)
-            .cpyi(((value >> 8) & 0xFF) as u8, target + 1)
-            .cpyi((value & 0xFF) as u8, target + 2)
+    pub fn cpyi_addr(&mut self, value: impl Into<Addr>, target: impl Into<Addr>) -> &mut Self {
+        self.try_cpyi_addr(value, target)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_cpyi_addr(
+        &mut self,
+        value: impl Into<Addr>,
+        target: impl Into<Addr>,
+    ) -> Result<&mut Self, BytePusherError> {
+        let id_table_addr = self
+            .get_id_table_addr()
+            .ok_or(BytePusherError::TableMissing(Table::Id))?;
+        self.check_space(27)?;
+
+        let (value, target) = (value.into(), target.into());
+        let op = move |rb: &Self| {
+            format!(
+                "cpyi_addr {}, {}",
+                rb.format_addr(value),
+                rb.format_addr(target)
+            )
+        };
+        self.emit(op, |rb| -> Result<(), BytePusherError> {
+            for (offset, shift) in [(0, 16), (1, 8), (2, 0)] {
+                let next = rb.get_next_instr_addr();
+                rb.try_write_table_ref(id_table_addr, shift, value)?
+                    .try_write_addr(target + offset)?
+                    .try_write_addr(next)?;
+            }
+            Ok(())
+        })?;
+        Ok(self)
     }
 
     pub fn db_arr(&mut self, data: &[u8]) -> &mut Self {
@@ -76,20 +274,235 @@ impl RomBuilder {
         self.write_u8(data)
     }
 
+    /// Increment the byte at the provided address, wrapping at 255
+    /// 18 bytes are written to the ROM
     pub fn inc(&mut self, addr: usize) -> &mut Self {
+        self.try_inc(addr).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_inc(&mut self, addr: usize) -> Result<&mut Self, BytePusherError> {
         let inc_table_addr = self
             .get_inc_table_addr()
-            .expect("Please install the increment table first using install_inc_table()");
+            .ok_or(BytePusherError::TableMissing(Table::Inc))?;
+        self.check_space(18)?;
+
+        self.emit(
+            |_| format!("inc 0x{:06X}", addr),
+            |rb| {
+                let lookup = rb.get_next_instr_addr() + 2;
+                rb.try_cpy(addr, lookup)?.try_cpy(inc_table_addr, addr)?;
+                Ok::<(), BytePusherError>(())
+            },
+        )?;
+        Ok(self)
+    }
+
+    /// Copy `len` bytes from `src` to `dst` with the default `BlockOptions`
+    /// Blocks that do not fit in a frame are continued in the following frames
+    pub fn memcpy(&mut self, src: usize, dst: usize, len: usize) -> &mut Self {
+        self.memcpy_with(src, dst, len, &BlockOptions::default())
+    }
 
-        self.cpy(addr, self.get_next_instr_addr() + 2)
-            .cpy(inc_table_addr, addr)
+    pub fn memcpy_with(
+        &mut self,
+        src: usize,
+        dst: usize,
+        len: usize,
+        options: &BlockOptions,
+    ) -> &mut Self {
+        self.try_memcpy_with(src, dst, len, options)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_memcpy_with(
+        &mut self,
+        src: usize,
+        dst: usize,
+        len: usize,
+        options: &BlockOptions,
+    ) -> Result<&mut Self, BytePusherError> {
+        let op = move |_: &Self| format!("memcpy 0x{:06X}, 0x{:06X}, {}", src, dst, len);
+        self.try_block(op, BlockSource::Copy(src), dst, len, options)
+    }
+
+    /// Fill `len` bytes at `dst` with an immediate value with the default `BlockOptions`
+    /// Blocks that do not fit in a frame are continued in the following frames
+    pub fn memset(&mut self, dst: usize, value: u8, len: usize) -> &mut Self {
+        self.memset_with(dst, value, len, &BlockOptions::default())
+    }
+
+    pub fn memset_with(
+        &mut self,
+        dst: usize,
+        value: u8,
+        len: usize,
+        options: &BlockOptions,
+    ) -> &mut Self {
+        self.try_memset_with(dst, value, len, options)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_memset_with(
+        &mut self,
+        dst: usize,
+        value: u8,
+        len: usize,
+        options: &BlockOptions,
+    ) -> Result<&mut Self, BytePusherError> {
+        let id_table_addr = self
+            .get_id_table_addr()
+            .ok_or(BytePusherError::TableMissing(Table::Id))?;
+        let op = move |_: &Self| format!("memset 0x{:06X}, 0x{:02X}, {}", dst, value, len);
+        let source = BlockSource::Fill(id_table_addr + value as usize);
+        self.try_block(op, source, dst, len, options)
+    }
+
+    /// Emit a block operation: whole pages run through a shared loop, the other bytes
+    /// are unrolled, and a `sync` is inserted whenever the frame budget is used up
+    ///
+    /// The loop writes one byte of the page per iteration, its index is the low byte
+    /// of the target (and source) address. Before every page the drivers write the
+    /// page addresses into the loop and its return address into the final jump.
+    fn try_block(
+        &mut self,
+        op: impl FnOnce(&Self) -> String,
+        source: BlockSource,
+        dst: usize,
+        len: usize,
+        options: &BlockOptions,
+    ) -> Result<&mut Self, BytePusherError> {
+        let aligned = dst.is_multiple_of(256)
+            && match source {
+                BlockSource::Copy(src) => src.is_multiple_of(256),
+                BlockSource::Fill(_) => true,
+            };
+        let pages = if aligned && len > options.unroll_limit {
+            len / 256
+        } else {
+            0
+        };
+        let (body, driver) = match source {
+            BlockSource::Copy(_) => (12, 8),
+            BlockSource::Fill(_) => (11, 6),
+        };
+        let page_cost = driver + 256 * body + 1;
+
+        let cost = |step: &BlockStep| match step {
+            BlockStep::Sync => SYNC_INSTRUCTIONS,
+            BlockStep::Page(_) => page_cost,
+            BlockStep::Byte(_) => 1,
+        };
+        let needed = if pages > 0 { page_cost } else { 1 } + SYNC_INSTRUCTIONS;
+        if needed > options.frame_budget {
+            return Err(BytePusherError::FrameBudgetExceeded {
+                needed,
+                budget: options.frame_budget,
+            });
+        }
+        let mut steps = Vec::new();
+        let mut used = if pages > 0 { 1 } else { 0 };
+        let parts = (0..pages)
+            .map(BlockStep::Page)
+            .chain((pages * 256..len).map(BlockStep::Byte));
+        for step in parts {
+            // Leave room for the sync continuing the block in the next frame
+            if used + cost(&step) + SYNC_INSTRUCTIONS > options.frame_budget {
+                steps.push(BlockStep::Sync);
+                used = 0;
+            }
+            used += cost(&step);
+            steps.push(step);
+        }
+
+        let syncs = steps
+            .iter()
+            .filter(|step| matches!(step, BlockStep::Sync))
+            .count();
+        if pages > 0 || syncs > 0 {
+            self.get_id_table_addr()
+                .ok_or(BytePusherError::TableMissing(Table::Id))?;
+        }
+        let loop_size = if pages > 0 {
+            (body + 2) * INSTR_SIZE
+        } else {
+            0
+        };
+        self.check_space(
+            loop_size
+                + pages * driver * INSTR_SIZE
+                + (len - pages * 256) * INSTR_SIZE
+                + syncs * SYNC_INSTRUCTIONS * INSTR_SIZE,
+        )?;
+        let inc_table_addr = if pages > 0 {
+            self.require_table(Table::Inc)?
+        } else {
+            0
+        };
+
+        let body_addr = self.get_current_addr() + INSTR_SIZE;
+        let exit = body_addr + body * INSTR_SIZE;
+        self.emit(op, |rb| -> Result<(), BytePusherError> {
+            if pages > 0 {
+                rb.try_jmp(exit + INSTR_SIZE)?;
+                // The page addresses are written by the drivers
+                match source {
+                    BlockSource::Copy(src) => rb.try_cpy(src, dst)?,
+                    BlockSource::Fill(entry) => rb.try_cpy(entry, dst)?,
+                };
+                let lookup = rb.get_next_instr_addr() + 2;
+                rb.try_cpy(body_addr + 5, lookup)?
+                    .try_cpy(inc_table_addr, body_addr + 5)?;
+                if let BlockSource::Copy(_) = source {
+                    rb.try_cpy(body_addr + 5, body_addr + 2)?;
+                }
+                rb.try_branch_if_zero(body_addr + 5, exit)?
+                    .try_jmp(body_addr)?;
+                // The return address is written by the drivers
+                rb.try_jmp(0x000000usize)?;
+            }
+            for step in steps {
+                match step {
+                    BlockStep::Sync => {
+                        rb.try_sync()?;
+                    }
+                    BlockStep::Page(page) => {
+                        let target = dst + page * 256;
+                        rb.try_cpyi((target >> 16) as u8, body_addr + 3)?
+                            .try_cpyi((target >> 8) as u8, body_addr + 4)?;
+                        if let BlockSource::Copy(src) = source {
+                            let source = src + page * 256;
+                            rb.try_cpyi((source >> 16) as u8, body_addr)?
+                                .try_cpyi((source >> 8) as u8, body_addr + 1)?;
+                        }
+                        let back = rb.new_internal_label("block.return");
+                        rb.try_cpyi_addr(back, exit + 6)?
+                            .try_jmp(body_addr)?
+                            .label(back);
+                    }
+                    BlockStep::Byte(i) => {
+                        match source {
+                            BlockSource::Copy(src) => rb.try_cpy(src + i, dst + i)?,
+                            BlockSource::Fill(entry) => rb.try_cpy(entry, dst + i)?,
+                        };
+                    }
+                }
+            }
+            Ok(())
+        })?;
+        Ok(self)
     }
 }
 
 #[cfg(test)]
 mod tests {
 
+    use super::BlockOptions;
+    use crate::error::BytePusherError;
+    use crate::regions::OverlapPolicy;
     use crate::rom_builder::RomBuilder;
+    use crate::tables::Table;
+    use crate::vm::Machine;
+    use crate::vm::testing::program;
 
     fn exec_bbj(rom: &mut RomBuilder, instr_addr: usize) -> usize {
         let read_addr = |offset| {
@@ -132,4 +545,195 @@ mod tests {
         }
         assert_eq!(rb[0x000100], 0x02);
     }
+
+    #[test]
+    fn test_forward_label_is_patched() {
+        let mut rb = RomBuilder::new();
+
+        rb.org(0x000000);
+        rb.install_id_table();
+
+        rb.org(0x000100);
+        let target = rb.new_label("target");
+        rb.cpyi_addr(target, 0x000010usize);
+        rb.jmp(target);
+        rb.nop();
+        rb.label(target);
+
+        assert!(rb.finalize().is_ok());
+        assert_eq!(rb[0x000100 + 27 + 6..0x000100 + 27 + 9], [0x00, 0x01, 0x2D]);
+
+        let mut pc = 0x000100;
+        for _ in 0..3 {
+            pc = exec_bbj(&mut rb, pc);
+        }
+        assert_eq!(rb[0x000010..0x000013], [0x00, 0x01, 0x2D]);
+        assert_eq!(exec_bbj(&mut rb, pc), 0x00012D);
+    }
+
+    #[test]
+    fn test_unresolved_labels_are_reported() {
+        let mut rb = RomBuilder::new();
+
+        let bound = rb.new_label("bound");
+        let missing = rb.new_label("missing");
+        rb.org(0x000100).label(bound).jmp(bound).jmp(missing);
+
+        match rb.finalize() {
+            Err(BytePusherError::UnresolvedLabels(names)) => assert_eq!(names, ["missing"]),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_fallible_variants_report_errors() {
+        let mut rb = RomBuilder::new();
+
+        assert!(matches!(
+            rb.org(0x000000).try_cpyi(0x01, 0x000005),
+            Err(BytePusherError::TableMissing(Table::Id))
+        ));
+        assert!(matches!(
+            rb.org(0x000010).try_install_inc_table(),
+            Err(BytePusherError::Misaligned {
+                addr: 0x000010,
+                alignment: 256,
+                ..
+            })
+        ));
+        assert!(rb.org(0x000100).try_install_inc_table().is_ok());
+        assert!(matches!(
+            rb.org(0x000200).try_install_inc_table(),
+            Err(BytePusherError::TableAlreadyInstalled(Table::Inc))
+        ));
+        assert!(matches!(
+            rb.org(0xFFFFF0).try_inc(0x000000),
+            Err(BytePusherError::AddressOutOfRange(0xFFFFF0))
+        ));
+        assert!(matches!(
+            rb.org(0xFFFFFF)
+                .try_write_u8(0xAA)
+                .and_then(|rb| rb.try_write_u8(0xBB)),
+            Err(BytePusherError::AddressOutOfRange(0x1000000))
+        ));
+        assert_eq!(rb[0xFFFFFF], 0xAA);
+        assert_eq!(
+            BytePusherError::TableMissing(Table::Dec).to_string(),
+            "Please install the Dec table first using install_table(Table::Dec)"
+        );
+    }
+
+    #[test]
+    fn test_fallible_variants_report_denied_overlaps() {
+        let mut rb = RomBuilder::new();
+        rb.set_overlap_policy(OverlapPolicy::Deny);
+        rb.org(0x000100).install_id_table().install_inc_table();
+        rb.section("data", 0x000400).db_arr(&[0; 64]);
+
+        rb.section("program", 0x000400);
+        assert!(matches!(
+            rb.try_cpyi(0x01, 0x000080),
+            Err(BytePusherError::Overlap(_))
+        ));
+        assert!(matches!(
+            rb.try_inc(0x000080),
+            Err(BytePusherError::Overlap(_))
+        ));
+        assert!(matches!(
+            rb.try_cpyi_addr(0x123456usize, 0x000080),
+            Err(BytePusherError::Overlap(_))
+        ));
+        assert!(matches!(
+            rb.try_jmp(0x000400usize),
+            Err(BytePusherError::Overlap(_))
+        ));
+        assert!(matches!(rb.try_sync(), Err(BytePusherError::Overlap(_))));
+        assert!(matches!(
+            rb.org(0x000400).try_install_table(Table::Dec),
+            Err(BytePusherError::Overlap(_))
+        ));
+        assert_eq!(rb.get_table_addr(Table::Dec), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Inc table already installed.")]
+    fn test_panicking_variants_keep_their_messages() {
+        let mut rb = RomBuilder::new();
+        assert_eq!(
+            rb.try_cpyi(0x01, 0x000005).err().unwrap().to_string(),
+            "Please install the identity table first using install_id_table()"
+        );
+        assert_eq!(
+            rb.org(0x000010)
+                .try_install_id_table()
+                .err()
+                .unwrap()
+                .to_string(),
+            "Id table needs to be 256-bytes memory aligned."
+        );
+        rb.org(0x000100).install_inc_table();
+        rb.org(0x000200).install_inc_table();
+    }
+
+    #[test]
+    fn test_block_copy_and_fill_in_vm() {
+        let mut rb = program();
+        let data: Vec<u8> = (0..528).map(|i| (i * 7 % 251) as u8).collect();
+        // The data is written first, so that the lazy tables follow it instead of
+        // landing on the destinations, which only the VM writes
+        rb.with_owner("data", |rb| {
+            rb.org(0x020000).db_arr(&data);
+        });
+        rb.org(0x008000).install_id_table().org(0x000100);
+
+        // Two looped pages that do not fit in a frame together, then unrolled bytes
+        let paged = BlockOptions::new().unroll_limit(0).frame_budget(5000);
+        rb.memcpy_with(0x020000, 0x010000, data.len(), &paged)
+            .memset(0x030010, 0xAB, 300)
+            .memset_with(0x040000, 0x5A, 256, &paged)
+            .wait();
+        assert!(matches!(
+            rb.try_memcpy_with(0x020000, 0x010000, 256, &paged.clone().frame_budget(100)),
+            Err(BytePusherError::FrameBudgetExceeded { budget: 100, .. })
+        ));
+
+        let mut machine = Machine::from_builder(&rb).unwrap();
+        machine.run_frame();
+        let memory = machine.memory();
+        assert_eq!(memory[0x010000..0x010100], data[..256]);
+        assert_eq!(memory[0x010100], 0);
+
+        machine.run_frames(2);
+        let memory = machine.memory();
+        assert_eq!(memory[0x010000..0x010000 + data.len()], data[..]);
+        assert_eq!(memory[0x010000 + data.len()], 0);
+        assert_eq!(memory[0x03000F], 0);
+        assert!(memory[0x030010..0x030010 + 300].iter().all(|&x| x == 0xAB));
+        assert_eq!(memory[0x030010 + 300], 0);
+        assert!(memory[0x040000..0x040100].iter().all(|&x| x == 0x5A));
+        assert_eq!(memory[0x040100], 0);
+        assert!(
+            rb.labels()
+                .all(|(name, _)| !name.starts_with("block.return"))
+        );
+    }
+
+    #[test]
+    fn test_block_reports_denied_overlaps() {
+        let mut rb = RomBuilder::new();
+        rb.set_overlap_policy(OverlapPolicy::Deny);
+        rb.org(0x008000).install_id_table();
+        rb.section("data", 0x000400).db_arr(&[0; 64]);
+
+        let paged = BlockOptions::new().unroll_limit(0);
+        rb.section("program", 0x000400);
+        assert!(matches!(
+            rb.try_memset_with(0x020000, 0x5A, 256, &paged),
+            Err(BytePusherError::Overlap(_))
+        ));
+        assert!(matches!(
+            rb.try_memcpy_with(0x010000, 0x020000, 16, &paged),
+            Err(BytePusherError::Overlap(_))
+        ));
+    }
 }
@@ -0,0 +1,294 @@
+use crate::error::BytePusherError;
+use crate::rom_builder::RomBuilder;
+use crate::rom_opcodes::INSTR_SIZE;
+use crate::tables::Table;
+
+/// Size of a `sync`
+const SYNC_SIZE: usize = 4 * INSTR_SIZE;
+
+/// Frames waited by a single counter loop
+const WAIT_LOOP_FRAMES: usize = 256;
+
+/// Largest code written by `wait_frames` for up to 256 frames
+pub const WAIT_FRAMES_MAX_SIZE: usize = 22 * INSTR_SIZE;
+
+// Frame timing
+// A frame ends at the first `wait`, so waiting n frames means running n `sync`.
+// Longer waits loop over a single `sync` with a down counter. Loop exits are chosen
+// without jump tables: the zero-detection table turns a counter into a 0/1 selector,
+// which picks the jump address bytes that differ between the two targets from the
+// scratch page of the multi-byte arithmetic.
+
+impl RomBuilder {
+    /// Wait `n` frames, with the same timing as `n` consecutive `sync`
+    /// Waits longer than a few frames share a counter byte allocated at the top of free memory
+    /// At most 198 bytes are written to the ROM for up to 256 frames
+    pub fn wait_frames(&mut self, n: usize) -> &mut Self {
+        self.try_wait_frames(n).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_wait_frames(&mut self, n: usize) -> Result<&mut Self, BytePusherError> {
+        if n == 0 {
+            return Err(BytePusherError::CountOutOfRange {
+                what: "wait_frames count",
+                count: n,
+                max: usize::MAX,
+            });
+        }
+        let id_table_addr = self
+            .get_id_table_addr()
+            .ok_or(BytePusherError::TableMissing(Table::Id))?;
+
+        // Split the wait in loops, short ones are cheaper as a chain of syncs
+        let mut chunks = Vec::new();
+        let mut addr = self.get_current_addr();
+        let mut left = n;
+        while left > 0 {
+            let frames = left.min(WAIT_LOOP_FRAMES);
+            let patched = wait_loop_patched_bytes(addr);
+            let loop_size = (10 + 4 * patched.len()) * INSTR_SIZE;
+            let size = if frames * SYNC_SIZE <= loop_size {
+                frames * SYNC_SIZE
+            } else {
+                loop_size
+            };
+            chunks.push((frames, size == loop_size, patched));
+            addr += size;
+            left -= frames;
+        }
+        self.check_space(addr - self.get_current_addr())?;
+        let (zero_table_addr, page, counter) = if chunks.iter().any(|&(_, looped, _)| looped) {
+            self.require_table(Table::Dec)?;
+            (
+                self.require_table(Table::Zero)?,
+                self.require_carry_page()?,
+                self.require_wait_counter()?,
+            )
+        } else {
+            (0, 0, 0)
+        };
+
+        self.emit(
+            |_| format!("wait_frames {}", n),
+            |rb| -> Result<(), BytePusherError> {
+                for (frames, looped, patched) in chunks {
+                    if !looped {
+                        for _ in 0..frames {
+                            rb.try_sync()?;
+                        }
+                        continue;
+                    }
+                    let start = rb.get_current_addr() + INSTR_SIZE;
+                    let exit = start + (9 + 4 * patched.len()) * INSTR_SIZE;
+                    // 256 frames wrap the counter around to 0
+                    rb.try_cpyi(frames as u8, counter)?;
+                    rb.try_sync()?.try_dec(counter)?;
+                    rb.zero_lookup(zero_table_addr, counter, page + 2)?
+                        .select_jump(id_table_addr, page, &patched, [start, exit])?;
+                }
+                Ok(())
+            },
+        )?;
+        Ok(self)
+    }
+
+    /// Emit `body` so that it runs on the first pass and then once every `n` passes,
+    /// for code running once per frame, e.g. to advance an animation every n frames
+    /// Each use keeps its counter in a byte allocated at the top of free memory
+    /// At most 171 bytes are written to the ROM, in addition to `body`
+    pub fn every_n_frames(&mut self, n: usize, body: impl FnOnce(&mut Self)) -> &mut Self {
+        self.try_every_n_frames(n, body)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_every_n_frames(
+        &mut self,
+        n: usize,
+        body: impl FnOnce(&mut Self),
+    ) -> Result<&mut Self, BytePusherError> {
+        if !(1..=256).contains(&n) {
+            return Err(BytePusherError::CountOutOfRange {
+                what: "every_n_frames period",
+                count: n,
+                max: 256,
+            });
+        }
+        let id_table_addr = self
+            .get_id_table_addr()
+            .ok_or(BytePusherError::TableMissing(Table::Id))?;
+
+        // A zero counter selects the body, other values are decremented and skip it
+        let current = self.get_current_addr();
+        let patched = patched_bytes(|len| {
+            let skip = current + (3 + 4 * len) * INSTR_SIZE;
+            [skip, skip + 3 * INSTR_SIZE]
+        });
+        let skip = current + (3 + 4 * patched.len()) * INSTR_SIZE;
+        let run = skip + 3 * INSTR_SIZE;
+        self.check_space(run - current + INSTR_SIZE)?;
+        self.require_table(Table::Dec)?;
+        let zero_table_addr = self.require_table(Table::Zero)?;
+        let page = self.require_carry_page()?;
+        let counter = self.alloc_top(1, 1)?;
+
+        let end = self.new_internal_label("every_n_frames.end");
+        self.emit(
+            |_| format!("every_n_frames {}", n),
+            |rb| -> Result<(), BytePusherError> {
+                rb.zero_lookup(zero_table_addr, counter, page + 2)?
+                    .select_jump(id_table_addr, page, &patched, [skip, run])?
+                    .try_dec(counter)?
+                    .try_jmp(end)?;
+                rb.try_cpyi((n - 1) as u8, counter)?;
+                Ok(())
+            },
+        )?;
+        body(self);
+        Ok(self.label(end))
+    }
+
+    /// Jump to `targets[selector]`, where selector is the carry byte of the scratch page (0 or 1)
+    /// Only the `patched` bytes of the jump address (0 is the high byte) are selected,
+    /// the others are those of `targets[0]`
+    /// 9 bytes plus 36 bytes for each patched byte are written to the ROM
+    fn select_jump(
+        &mut self,
+        id_table_addr: usize,
+        page: usize,
+        patched: &[usize],
+        targets: [usize; 2],
+    ) -> Result<&mut Self, BytePusherError> {
+        let jump = self.get_current_addr() + 4 * patched.len() * INSTR_SIZE;
+        for &offset in patched {
+            let shift = 16 - 8 * offset;
+            self.try_cpy(id_table_addr + ((targets[0] >> shift) & 0xFF), page)?
+                .try_cpy(id_table_addr + ((targets[1] >> shift) & 0xFF), page + 1)?
+                .select(page, page + 2, jump + 6 + offset)?;
+        }
+        self.try_jmp(targets[0])
+    }
+
+    /// Counter byte of the `wait_frames` loops, allocated on first use
+    /// Waits run one after the other, so a single counter serves all of them
+    fn require_wait_counter(&mut self) -> Result<usize, BytePusherError> {
+        if let Some(counter) = self.wait_counter {
+            return Ok(counter);
+        }
+        let counter = self.alloc_top(1, 1)?;
+        self.wait_counter = Some(counter);
+        Ok(counter)
+    }
+}
+
+/// Jump address bytes patched by the `wait_frames` loop starting at `addr`
+fn wait_loop_patched_bytes(addr: usize) -> Vec<usize> {
+    let start = addr + INSTR_SIZE;
+    patched_bytes(|len| [start, start + (9 + 4 * len) * INSTR_SIZE])
+}
+
+/// Bytes that differ between two jump targets, whose addresses depend on the number
+/// of patched bytes; all of them are patched when no smaller set is consistent
+fn patched_bytes(targets: impl Fn(usize) -> [usize; 2]) -> Vec<usize> {
+    for len in 1..3 {
+        let [a, b] = targets(len);
+        let differing: Vec<usize> = (0..3)
+            .filter(|offset| {
+                let shift = 16 - 8 * offset;
+                (a >> shift) & 0xFF != (b >> shift) & 0xFF
+            })
+            .collect();
+        if differing.len() == len {
+            return differing;
+        }
+    }
+    vec![0, 1, 2]
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::BytePusherError;
+    use crate::regions::OverlapPolicy;
+    use crate::rom_builder::RomBuilder;
+    use crate::vm::Machine;
+    use crate::vm::testing::program;
+
+    #[test]
+    fn test_wait_frames_in_vm() {
+        let mut rb = program();
+        let (marks, ticks) = (0x000080, 0x000090);
+        rb.org(0x008000)
+            .install_id_table()
+            .install_inc_table()
+            .org(0x000100);
+
+        // Marks are set after 2, 7 and 307 frames
+        let main = rb.new_label("main");
+        rb.wait_frames(2)
+            .cpyi(1, marks)
+            .wait_frames(5)
+            .cpyi(2, marks + 1)
+            .wait_frames(300)
+            .cpyi(3, marks + 2);
+        rb.label(main)
+            .every_n_frames(3, |rb| {
+                rb.inc(ticks);
+            })
+            .sync()
+            .jmp(main);
+        assert!(matches!(
+            rb.try_wait_frames(0),
+            Err(BytePusherError::CountOutOfRange { count: 0, .. })
+        ));
+        assert!(matches!(
+            rb.try_every_n_frames(0, |_| {}),
+            Err(BytePusherError::CountOutOfRange { count: 0, .. })
+        ));
+
+        let mut machine = Machine::from_builder(&rb).unwrap();
+        let mut history = Vec::new();
+        for _ in 0..320 {
+            machine.run_frame();
+            let memory = machine.memory();
+            history.push([memory[marks], memory[marks + 1], memory[marks + 2]]);
+        }
+        assert_eq!(history[1], [0, 0, 0]);
+        assert_eq!(history[2], [1, 0, 0]);
+        assert_eq!(history[6], [1, 0, 0]);
+        assert_eq!(history[7], [1, 2, 0]);
+        assert_eq!(history[306], [1, 2, 0]);
+        assert_eq!(history[307], [1, 2, 3]);
+        // The main loop starts in frame 307, then runs once per frame
+        assert_eq!(machine.memory()[ticks], 5);
+    }
+
+    #[test]
+    fn test_wait_frames_share_their_counter() {
+        let mut rb = program();
+        rb.org(0x008000).install_id_table().org(0x000100);
+        rb.wait_frames(300).wait_frames(300);
+        let counter = rb.wait_counter.unwrap();
+        assert_eq!(rb.alloc_top(1, 1).unwrap(), counter - 1);
+    }
+
+    #[test]
+    fn test_timing_reports_denied_overlaps() {
+        let mut rb = RomBuilder::new();
+        rb.set_overlap_policy(OverlapPolicy::Deny);
+        rb.org(0x000100).install_id_table();
+        rb.section("data", 0x000400).db_arr(&[0; 64]);
+
+        rb.section("program", 0x000400);
+        assert!(matches!(
+            rb.try_wait_frames(2),
+            Err(BytePusherError::Overlap(_))
+        ));
+        assert!(matches!(
+            rb.try_wait_frames(300),
+            Err(BytePusherError::Overlap(_))
+        ));
+        assert!(matches!(
+            rb.try_every_n_frames(3, |_| {}),
+            Err(BytePusherError::Overlap(_))
+        ));
+    }
+}
@@ -0,0 +1,221 @@
+use rand::Rng;
+
+use crate::error::BytePusherError;
+use crate::rom_builder::{RomBuilder, SCREEN_PAGE_SIZE, SCREEN_REGISTER_ADDR};
+use crate::rom_opcodes::INSTR_SIZE;
+use crate::tables::Table;
+
+/// Still screen of random pixels, drawn from the given generator
+pub fn random_pattern<R: Rng>(rng: &mut R) -> Result<RomBuilder, BytePusherError> {
+    let mut rm = RomBuilder::new();
+
+    // Memory layout
+    let screen_start = rm.alloc_screen_page()?;
+    let audio_start = rm.alloc_audio_page()?;
+    let program_start = rm.alloc_program(INSTR_SIZE)?;
+
+    // Initialize registers
+    rm.init_regs(0x0000, program_start, screen_start, audio_start);
+
+    // ROM logic
+    rm.section("program", program_start).wait();
+
+    // No sound dummy samples
+    rm.section("audio", audio_start).db_arr(&[0; 256]);
+
+    // Generate random pixels
+    rm.section("screen", screen_start);
+    for _ in 0..65536 {
+        rm.db(rng.random::<u8>() % 217);
+    }
+
+    Ok(rm)
+}
+
+/// Still screen showing an image already converted to palette indices
+pub fn still_image(image: &[u8]) -> Result<RomBuilder, BytePusherError> {
+    let mut rm = RomBuilder::new();
+
+    // Memory layout
+    let screen_start = rm.alloc_screen_page()?;
+    let audio_start = rm.alloc_audio_page()?;
+    let program_start = rm.alloc_program(INSTR_SIZE)?;
+
+    // Initialize registers
+    rm.init_regs(0x0000, program_start, screen_start, audio_start);
+
+    // ROM logic
+    rm.section("program", program_start).wait();
+
+    // No sound dummy samples
+    rm.section("audio", audio_start).db_arr(&[0; 256]);
+
+    // Add image data
+    rm.section("screen", screen_start).db_arr(image);
+
+    Ok(rm)
+}
+
+/// Default number of frames each video frame is shown by `video`
+pub const VIDEO_HOLD_FRAMES: usize = 4;
+
+/// Video playback, one 64 KiB screen page per frame, each shown for `hold_frames` frames
+pub fn video(video: &[u8], hold_frames: usize) -> Result<RomBuilder, BytePusherError> {
+    let mut rm = RomBuilder::new();
+
+    let frame_count = video.len() / SCREEN_PAGE_SIZE;
+    if !(1..=255).contains(&frame_count) {
+        return Err(BytePusherError::CountOutOfRange {
+            what: "Video frame count",
+            count: frame_count,
+            max: 255,
+        });
+    }
+
+    // Memory layout: the program is placed once emitted
+    let screen_start = rm.alloc_screen_pages(frame_count)?;
+    let audio_start = rm.alloc_audio_page()?;
+    let kernel_start = rm.alloc(1024, 256)?;
+
+    // install kernel tables
+    rm.org(kernel_start);
+    rm.install_id_table();
+    rm.install_inc_table();
+    rm.install_table(Table::Dec);
+    rm.install_table(Table::Zero);
+
+    // ROM logic: a wait and an inc per frame, the last frame is held as long
+    // before a cpyi brings back the first one and a jmp restarts the loop
+    let program_start = rm.alloc_program_with(|rm, program_start| {
+        rm.section("program", program_start);
+        for _ in 1..frame_count {
+            rm.try_wait_frames(hold_frames)?
+                .try_inc(SCREEN_REGISTER_ADDR)?;
+        }
+        rm.try_wait_frames(hold_frames)?
+            .try_cpyi((screen_start >> 16) as u8, SCREEN_REGISTER_ADDR)?
+            .try_jmp(program_start)?;
+        Ok(())
+    })?;
+
+    // Initialize registers
+    rm.org(0x000000)
+        .init_regs(0x0000, program_start, screen_start, audio_start);
+
+    // No sound dummy samples
+    rm.section("audio", audio_start).db_arr(&[0; 256]);
+
+    // Add video frames
+    rm.section("screen", screen_start).db_arr(video);
+
+    Ok(rm)
+}
+
+/// Number of noise frames generated by `animated_noise`
+pub const NOISE_FRAMES: usize = 4;
+
+/// Default number of frames each noise frame is shown by `animated_noise`
+pub const NOISE_HOLD_FRAMES: usize = 4;
+
+/// Animated noise cycling through 4 pre-generated frames, each shown for `hold_frames` frames,
+/// see `src/bin/rom-animated-noise.rs` for details
+pub fn animated_noise(hold_frames: usize) -> Result<RomBuilder, BytePusherError> {
+    let mut rm = RomBuilder::new();
+
+    // Memory layout: frame data, audio samples, kernel tables (ID, INC, DEC and ZERO)
+    // and the animation loop, placed once emitted
+    let screen_start = rm.alloc_screen_pages(NOISE_FRAMES)?;
+    let audio_start = rm.alloc_audio_page()?;
+    let kernel_start = rm.alloc(1024, 256)?;
+
+    // Install kernel tables
+    rm.org(kernel_start);
+    rm.install_id_table();
+    rm.install_inc_table();
+    rm.install_table(Table::Dec);
+    rm.install_table(Table::Zero);
+
+    // ROM logic - Animation loop
+    // This loop cycles through 4 frames, waiting hold_frames frames between each frame
+    // to achieve a slower, more visible animation speed
+    let program_start = rm.alloc_program_with(|rm, program_start| {
+        rm.section("program", program_start);
+
+        // Animation loop: cycle through frames
+        // Each iteration: wait hold_frames frames then switch to next frame
+        for frame_idx in 0..NOISE_FRAMES {
+            let frame_addr = screen_start + (frame_idx * SCREEN_PAGE_SIZE);
+
+            // Wait for several frames to slow down animation
+            rm.try_wait_frames(hold_frames)?;
+
+            // Set screen register to point to this frame (only write high byte)
+            // Screen register is 1 byte at 0x000005, we must not overwrite audio register at 0x000006
+            rm.try_cpyi((frame_addr >> 16) as u8, SCREEN_REGISTER_ADDR)?;
+        }
+
+        // Jump back to start of animation loop
+        rm.jmp(program_start);
+        Ok(())
+    })?;
+
+    // Initialize registers
+    rm.org(0x000000)
+        .init_regs(0x0000, program_start, screen_start, audio_start);
+
+    // No sound - dummy samples
+    rm.section("audio", audio_start).db_arr(&[0; 256]);
+
+    // Generate random noise frames
+    // Uses different seeds for each frame to ensure variety
+    for frame in 0..NOISE_FRAMES {
+        rm.section(
+            &format!("screen/frame_{:03}", frame),
+            screen_start + frame * SCREEN_PAGE_SIZE,
+        );
+
+        // Use different seed for each frame to ensure variety
+        let mut seed = (frame as u32 * 12345 + 67890) as u64;
+
+        for _ in 0..65536 {
+            // Simple but effective LCG (Linear Congruential Generator)
+            // Constants from Numerical Recipes: a=1103515245, c=12345
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            let random_value = ((seed >> 16) & 0xFF) as u8;
+
+            // Modulo 216 to fit BytePusher's 6x6x6 RGB palette (216 colors)
+            rm.db(random_value % 216);
+        }
+    }
+
+    Ok(rm)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vm::Machine;
+
+    #[test]
+    fn test_video_needs_frames() {
+        assert!(matches!(
+            video(&[], VIDEO_HOLD_FRAMES),
+            Err(BytePusherError::CountOutOfRange { count: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn test_video_shows_every_frame() {
+        let frames: Vec<u8> = (1..=3).flat_map(|i| [i; SCREEN_PAGE_SIZE]).collect();
+        let rom = video(&frames, 2).unwrap();
+        let mut machine = Machine::from_builder(&rom).unwrap();
+
+        let mut shown = Vec::new();
+        for _ in 0..8 {
+            machine.run_frame();
+            let screen = machine.memory()[SCREEN_REGISTER_ADDR] as usize * SCREEN_PAGE_SIZE;
+            shown.push(machine.memory()[screen]);
+        }
+        assert_eq!(shown, [1, 1, 2, 2, 3, 3, 1, 1]);
+    }
+}
@@ -0,0 +1,137 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// How much of the 16 MiB address space is written to the ROM file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PaddingPolicy {
+    /// Stop at the last non-zero byte, trailing black pixels or silence are lost
+    #[default]
+    TrimZeros,
+    /// Write the whole 16 MiB address space
+    Full,
+    /// Stop at the end of the last written region, zeros included
+    LastWritten,
+    /// Pad the last written region to a multiple of 64 KiB
+    Align64K,
+}
+
+impl PaddingPolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PaddingPolicy::TrimZeros => "trim-zeros",
+            PaddingPolicy::Full => "full",
+            PaddingPolicy::LastWritten => "last-written",
+            PaddingPolicy::Align64K => "align-64k",
+        }
+    }
+}
+
+impl fmt::Display for PaddingPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for PaddingPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "trim-zeros" => Ok(PaddingPolicy::TrimZeros),
+            "full" => Ok(PaddingPolicy::Full),
+            "last-written" => Ok(PaddingPolicy::LastWritten),
+            "align-64k" => Ok(PaddingPolicy::Align64K),
+            _ => Err(format!(
+                "unknown padding policy '{}' (expected trim-zeros, full, last-written or align-64k)",
+                s
+            )),
+        }
+    }
+}
+
+/// Options of `RomBuilder::save_with_options`
+#[derive(Debug, Clone, Default)]
+pub struct SaveOptions {
+    pub padding: PaddingPolicy,
+    /// Path of the listing written next to the ROM, if any
+    pub listing: Option<String>,
+}
+
+impl SaveOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn padding(mut self, padding: PaddingPolicy) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    pub fn listing(mut self, path: impl Into<String>) -> Self {
+        self.listing = Some(path.into());
+        self
+    }
+}
+
+/// Command line flag selecting the padding of the saved ROM, flattened into the
+/// arguments of the binaries with `#[command(flatten)]`
+#[derive(clap::Args, Debug, Clone, Copy)]
+#[command(about = None, long_about = None)]
+pub struct PaddingArgs {
+    /// Padding of the saved ROM: trim-zeros, full, last-written or align-64k
+    #[arg(long = "padding", default_value_t = PaddingPolicy::TrimZeros)]
+    pub policy: PaddingPolicy,
+}
+
+impl PaddingArgs {
+    /// Save options with the selected padding
+    pub fn save_options(&self) -> SaveOptions {
+        SaveOptions::new().padding(self.policy)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rom_builder::{MEMORY_SIZE, RomBuilder};
+
+    #[test]
+    fn test_padding_policies() {
+        let mut rb = RomBuilder::new();
+        rb.org(0x000100).db_arr(&[1, 2, 0, 0]);
+
+        assert_eq!(rb.saved_len(PaddingPolicy::TrimZeros), 0x000102);
+        assert_eq!(rb.saved_len(PaddingPolicy::LastWritten), 0x000104);
+        assert_eq!(rb.saved_len(PaddingPolicy::Align64K), 0x010000);
+        assert_eq!(rb.saved_len(PaddingPolicy::Full), MEMORY_SIZE);
+
+        rb[0x010000..0x010002].copy_from_slice(&[3, 0]);
+        assert_eq!(rb.saved_len(PaddingPolicy::LastWritten), 0x010002);
+        assert_eq!(rb.saved_len(PaddingPolicy::Align64K), 0x020000);
+
+        assert_eq!("align-64k".parse(), Ok(PaddingPolicy::Align64K));
+        assert_eq!(
+            PaddingPolicy::LastWritten.to_string().parse(),
+            Ok(PaddingPolicy::LastWritten)
+        );
+        assert!("pad".parse::<PaddingPolicy>().is_err());
+    }
+
+    #[test]
+    fn test_save_writes_listing() {
+        let dir = std::env::temp_dir();
+        let rom = dir.join(format!("save-listing-{}.BytePusher", std::process::id()));
+        let lst = rom.with_extension("lst");
+
+        let mut rb = RomBuilder::new();
+        rb.org(0x000100).nop();
+        let options = SaveOptions::new().listing(lst.to_str().unwrap());
+        rb.save_with_options(rom.to_str().unwrap(), &options)
+            .unwrap();
+
+        let listing = std::fs::read_to_string(&lst).unwrap();
+        assert!(listing.contains("nop"));
+        std::fs::remove_file(rom).unwrap();
+        std::fs::remove_file(lst).unwrap();
+    }
+}
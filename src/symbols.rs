@@ -0,0 +1,175 @@
+use std::collections::HashSet;
+use std::fmt::{self, Write};
+
+use crate::error::BytePusherError;
+use crate::memory_map::json_escape;
+use crate::rom_builder::RomBuilder;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SymbolKind {
+    Table,
+    Section,
+    Label,
+}
+
+impl SymbolKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SymbolKind::Table => "table",
+            SymbolKind::Section => "section",
+            SymbolKind::Label => "label",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symbol {
+    pub addr: usize,
+    pub name: String,
+    pub kind: SymbolKind,
+}
+
+/// Named addresses of a ROM, to be loaded by emulators and debuggers
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SymbolTable {
+    /// Symbols ordered by address, names are unique
+    pub symbols: Vec<Symbol>,
+}
+
+impl SymbolTable {
+    pub fn from_builder(rom: &RomBuilder) -> Self {
+        let mut symbols = Vec::new();
+
+        for section in rom.memory_map().sections {
+            symbols.push(Symbol {
+                addr: section.start,
+                name: section.name,
+                kind: SymbolKind::Section,
+            });
+        }
+
+        for (table, addr) in rom.tables() {
+            symbols.push(Symbol {
+                addr,
+                name: table.owner().to_string(),
+                kind: SymbolKind::Table,
+            });
+        }
+
+        // Repeated names get a numeric suffix, skipping the names of other labels
+        let names: HashSet<&str> = rom.labels().map(|(name, _)| name).collect();
+        let mut taken: HashSet<String> = HashSet::new();
+        for (name, addr) in rom.labels() {
+            let mut unique = name.to_string();
+            let mut suffix = 0;
+            while taken.contains(&unique) || (suffix > 0 && names.contains(unique.as_str())) {
+                suffix += 1;
+                unique = format!("{}.{}", name, suffix);
+            }
+            taken.insert(unique.clone());
+
+            if let Some(addr) = addr {
+                symbols.push(Symbol {
+                    addr,
+                    name: unique,
+                    kind: SymbolKind::Label,
+                });
+            }
+        }
+
+        // Tables are also sections named after them, keep the table symbol only
+        symbols.sort_by(|a, b| (a.addr, &a.name, a.kind).cmp(&(b.addr, &b.name, b.kind)));
+        symbols.dedup_by(|a, b| a.addr == b.addr && a.name == b.name);
+
+        Self { symbols }
+    }
+
+    /// Symbols in the `.sym` format, one `address name` pair per line
+    pub fn to_sym(&self) -> String {
+        self.to_string()
+    }
+
+    pub fn to_json(&self) -> String {
+        let mut json = String::from("{\n  \"symbols\": [\n");
+        for (i, symbol) in self.symbols.iter().enumerate() {
+            let _ = write!(
+                json,
+                "    {{\"name\": \"{}\", \"addr\": {}, \"kind\": \"{}\"}}",
+                json_escape(&symbol.name),
+                symbol.addr,
+                symbol.kind.as_str()
+            );
+            json.push_str(if i + 1 < self.symbols.len() {
+                ",\n"
+            } else {
+                "\n"
+            });
+        }
+        json.push_str("  ]\n}\n");
+        json
+    }
+
+    pub fn save_sym(&self, filename: &str) -> Result<(), BytePusherError> {
+        std::fs::write(filename, self.to_sym())?;
+        Ok(())
+    }
+
+    pub fn save_json(&self, filename: &str) -> Result<(), BytePusherError> {
+        std::fs::write(filename, self.to_json())?;
+        Ok(())
+    }
+}
+
+impl fmt::Display for SymbolTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for symbol in &self.symbols {
+            writeln!(f, "{:06X} {}", symbol.addr, symbol.name)?;
+        }
+        Ok(())
+    }
+}
+
+impl RomBuilder {
+    /// Labels, sections and kernel tables of the ROM
+    pub fn symbols(&self) -> SymbolTable {
+        SymbolTable::from_builder(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_symbols_from_labels_sections_and_tables() {
+        let mut rb = RomBuilder::new();
+        rb.init_regs(0x0000, 0x000200, 0x010000, 0x000300);
+        rb.org(0x000100).install_id_table();
+
+        rb.section("program", 0x000200);
+        let start = rb.new_label("start");
+        rb.label(start).sync().sync();
+        let again = rb.new_label("start");
+        rb.label(again).jmp(start);
+        let suffixed = rb.new_label("start.1");
+        rb.label(suffixed).jmp(again);
+        rb.new_label("unbound");
+
+        let symbols = rb.symbols();
+        assert_eq!(
+            symbols.to_sym(),
+            "000000 registers\n\
+             000100 id_table\n\
+             000200 program\n\
+             000200 start\n\
+             000248 start.2\n\
+             000251 start.1\n"
+        );
+        assert_eq!(symbols.symbols[1].kind, SymbolKind::Table);
+        assert!(
+            symbols
+                .to_json()
+                .contains("{\"name\": \"start\", \"addr\": 512, \"kind\": \"label\"},")
+        );
+    }
+}
@@ -0,0 +1,123 @@
+/// Lookup tables used by the opcodes
+/// Unary tables have 256 entries, binary ones 256x256 entries with the first
+/// operand selecting the row, so that both operands can be patched into an address
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Table {
+    /// x
+    Id,
+    /// x + 1
+    Inc,
+    /// x - 1
+    Dec,
+    /// a + b
+    Add,
+    /// a - b
+    Sub,
+    /// a & b
+    And,
+    /// a | b
+    Or,
+    /// a ^ b
+    Xor,
+    /// !x
+    Not,
+    /// x << 1
+    Shl,
+    /// x >> 1
+    Shr,
+    /// 1 if a < b, 0 otherwise
+    Lt,
+    /// 1 if x is zero, 0 otherwise
+    Zero,
+}
+
+impl Table {
+    /// Name used in error messages, e.g. "Id table not installed"
+    pub fn name(&self) -> &'static str {
+        match self {
+            Table::Id => "Id",
+            Table::Inc => "Inc",
+            Table::Dec => "Dec",
+            Table::Add => "Add",
+            Table::Sub => "Sub",
+            Table::And => "And",
+            Table::Or => "Or",
+            Table::Xor => "Xor",
+            Table::Not => "Not",
+            Table::Shl => "Shl",
+            Table::Shr => "Shr",
+            Table::Lt => "Lt",
+            Table::Zero => "Zero",
+        }
+    }
+
+    /// Description used in alignment errors
+    pub fn title(&self) -> &'static str {
+        match self {
+            Table::Id => "Id table",
+            Table::Inc => "Inc table",
+            Table::Dec => "Dec table",
+            Table::Add => "Add table",
+            Table::Sub => "Sub table",
+            Table::And => "And table",
+            Table::Or => "Or table",
+            Table::Xor => "Xor table",
+            Table::Not => "Not table",
+            Table::Shl => "Shl table",
+            Table::Shr => "Shr table",
+            Table::Lt => "Lt table",
+            Table::Zero => "Zero table",
+        }
+    }
+
+    /// Owner tag of the table bytes, also used as symbol name
+    pub fn owner(&self) -> &'static str {
+        match self {
+            Table::Id => "id_table",
+            Table::Inc => "inc_table",
+            Table::Dec => "dec_table",
+            Table::Add => "add_table",
+            Table::Sub => "sub_table",
+            Table::And => "and_table",
+            Table::Or => "or_table",
+            Table::Xor => "xor_table",
+            Table::Not => "not_table",
+            Table::Shl => "shl_table",
+            Table::Shr => "shr_table",
+            Table::Lt => "lt_table",
+            Table::Zero => "zero_table",
+        }
+    }
+
+    pub fn is_binary(&self) -> bool {
+        matches!(
+            self,
+            Table::Add | Table::Sub | Table::And | Table::Or | Table::Xor | Table::Lt
+        )
+    }
+
+    /// Size of the table, it is also its alignment
+    pub fn size(&self) -> usize {
+        if self.is_binary() { 65536 } else { 256 }
+    }
+
+    /// Entry at `index`, binary tables are indexed with `(a << 8) | b`
+    pub fn entry(&self, index: usize) -> u8 {
+        let (a, b) = ((index >> 8) as u8, index as u8);
+        match self {
+            Table::Id => b,
+            Table::Inc => b.wrapping_add(1),
+            Table::Dec => b.wrapping_sub(1),
+            Table::Add => a.wrapping_add(b),
+            Table::Sub => a.wrapping_sub(b),
+            Table::And => a & b,
+            Table::Or => a | b,
+            Table::Xor => a ^ b,
+            Table::Not => !b,
+            Table::Shl => b << 1,
+            Table::Shr => b >> 1,
+            Table::Lt => (a < b) as u8,
+            Table::Zero => (b == 0) as u8,
+        }
+    }
+}
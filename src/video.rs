@@ -128,15 +128,16 @@ fn apply_floyd_steinberg_dither(
 
 /// Distribuisce l'errore di quantizzazione usando l'algoritmo Floyd-Steinberg
 fn distribute_error(
-    error_buffer: &mut Vec<Vec<[f32; 3]>>,
+    error_buffer: &mut [Vec<[f32; 3]>],
     x: u32,
     y: u32,
     width: u32,
     height: u32,
     quant_error: [f32; 3],
 ) {
+    #[allow(clippy::too_many_arguments)]
     fn distribute(
-        error_buffer: &mut Vec<Vec<[f32; 3]>>,
+        error_buffer: &mut [Vec<[f32; 3]>],
         x: u32,
         y: u32,
         width: u32,
@@ -254,14 +255,15 @@ fn process_single_image(
 /// Un `Vec<ProcessedFrame>` contenente tutti i frame elaborati, ordinati per nome file
 ///
 /// # Esempio
-/// ```rust
-/// use bytepusher_lib::process_png_sequence;
+/// ```rust,no_run
+/// use rustedbytes_bytepusher_rombuilder::video::process_png_sequence;
 ///
 /// let frames = process_png_sequence("input/*.png")?;
 /// for frame in frames {
 ///     println!("Frame {}: {}x{} pixels, {} bytes",
 ///              frame.frame_index, frame.width, frame.height, frame.rgb_data.len());
 /// }
+/// # Ok::<(), rustedbytes_bytepusher_rombuilder::error::BytePusherError>(())
 /// ```
 pub fn process_png_sequence(glob_pattern: &str) -> Result<Vec<ProcessedFrame>, BytePusherError> {
     // Genera la palette BytePusher
@@ -385,8 +387,8 @@ mod tests {
         assert_eq!(palette[215], [255, 255, 255]); // Bianco
 
         // Verifica alcuni colori intermedi
-        assert_eq!(palette[35], [255, 0, 0]); // Rosso puro
-        assert_eq!(palette[210], [0, 255, 0]); // Verde puro  
+        assert_eq!(palette[180], [255, 0, 0]); // Rosso puro
+        assert_eq!(palette[30], [0, 255, 0]); // Verde puro
     }
 
     #[test]
@@ -0,0 +1,213 @@
+use crate::error::BytePusherError;
+use crate::rom_builder::{
+    AUDIO_REGISTER_ADDR, KEYBOARD_REGISTER_ADDR, MEMORY_SIZE, PROGRAM_COUNTER_ADDR, RomBuilder,
+    SCREEN_REGISTER_ADDR,
+};
+
+/// Number of ByteByteJump instructions executed in each frame
+pub const INSTRUCTIONS_PER_FRAME: usize = 65536;
+
+/// Size of the screen page (256x256 pixels, one byte per pixel)
+pub const SCREEN_SIZE: usize = 65536;
+
+/// Number of 8-bit audio samples played in each frame
+pub const AUDIO_SAMPLES_PER_FRAME: usize = 256;
+
+/// Extra bytes after the address space, so that an instruction fetched
+/// at the very end of memory can still be decoded
+const MEMORY_PADDING: usize = 8;
+
+/// Headless BytePusher virtual machine
+pub struct Machine {
+    memory: Vec<u8>,
+    keys: u16,
+    frame_count: usize,
+}
+
+impl Default for Machine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Machine {
+    /// Create a machine with zeroed memory
+    pub fn new() -> Self {
+        Self {
+            memory: vec![0; MEMORY_SIZE + MEMORY_PADDING],
+            keys: 0,
+            frame_count: 0,
+        }
+    }
+
+    /// Create a machine and load the ROM image at address 0
+    pub fn from_bytes(rom: &[u8]) -> Result<Self, BytePusherError> {
+        if rom.len() > MEMORY_SIZE {
+            return Err(BytePusherError::InvalidFormat);
+        }
+
+        let mut machine = Self::new();
+        machine.memory[..rom.len()].copy_from_slice(rom);
+        Ok(machine)
+    }
+
+    /// Create a machine and load the content of a `.BytePusher` file
+    pub fn from_file(filename: &str) -> Result<Self, BytePusherError> {
+        Self::from_bytes(&std::fs::read(filename)?)
+    }
+
+    /// Create a machine running the ROM currently held by the builder
+    pub fn from_builder(rom: &RomBuilder) -> Result<Self, BytePusherError> {
+        rom.finalize()?;
+        Self::from_bytes(&rom.to_bytes())
+    }
+
+    /// Set the keys held down from the next frame on (bit N is key N)
+    pub fn set_keys(&mut self, keys: u16) {
+        self.keys = keys;
+    }
+
+    pub fn keys(&self) -> u16 {
+        self.keys
+    }
+
+    /// Number of frames executed so far
+    pub fn frame_count(&self) -> usize {
+        self.frame_count
+    }
+
+    pub fn memory(&self) -> &[u8] {
+        &self.memory[..MEMORY_SIZE]
+    }
+
+    pub fn memory_mut(&mut self) -> &mut [u8] {
+        &mut self.memory[..MEMORY_SIZE]
+    }
+
+    fn read_u24(&self, addr: usize) -> usize {
+        ((self.memory[addr] as usize) << 16)
+            | ((self.memory[addr + 1] as usize) << 8)
+            | (self.memory[addr + 2] as usize)
+    }
+
+    /// Address the program counter register points to
+    pub fn program_counter(&self) -> usize {
+        self.read_u24(PROGRAM_COUNTER_ADDR)
+    }
+
+    /// Start address of the screen page selected by the screen register
+    pub fn screen_addr(&self) -> usize {
+        (self.memory[SCREEN_REGISTER_ADDR] as usize) << 16
+    }
+
+    /// Start address of the audio samples selected by the audio register
+    pub fn audio_addr(&self) -> usize {
+        ((self.memory[AUDIO_REGISTER_ADDR] as usize) << 16)
+            | ((self.memory[AUDIO_REGISTER_ADDR + 1] as usize) << 8)
+    }
+
+    /// Pixels of the current screen page (palette indices)
+    pub fn screen(&self) -> &[u8] {
+        let addr = self.screen_addr();
+        &self.memory[addr..addr + SCREEN_SIZE]
+    }
+
+    /// Signed 8-bit samples of the current audio page
+    pub fn audio(&self) -> &[u8] {
+        let addr = self.audio_addr();
+        &self.memory[addr..addr + AUDIO_SAMPLES_PER_FRAME]
+    }
+
+    /// Execute a single ByteByteJump instruction and return the jump address
+    pub fn step(&mut self, pc: usize) -> usize {
+        let source = self.read_u24(pc);
+        let target = self.read_u24(pc + 3);
+        self.memory[target] = self.memory[source];
+        self.read_u24(pc + 6)
+    }
+
+    /// Run one frame: publish the keyboard state, then execute
+    /// 65536 instructions starting from the program counter register
+    pub fn run_frame(&mut self) {
+        self.memory[KEYBOARD_REGISTER_ADDR] = (self.keys >> 8) as u8;
+        self.memory[KEYBOARD_REGISTER_ADDR + 1] = (self.keys & 0xFF) as u8;
+
+        let mut pc = self.program_counter();
+        for _ in 0..INSTRUCTIONS_PER_FRAME {
+            pc = self.step(pc);
+        }
+        self.frame_count += 1;
+    }
+
+    /// Run the given number of frames
+    pub fn run_frames(&mut self, frames: usize) {
+        for _ in 0..frames {
+            self.run_frame();
+        }
+    }
+}
+
+/// Helpers running short builder programs in the machine
+#[cfg(test)]
+pub(crate) mod testing {
+    use super::Machine;
+    use crate::rom_builder::RomBuilder;
+
+    /// Builder with the program starting at 0x000100, addresses below are free for data
+    pub(crate) fn program() -> RomBuilder {
+        let mut rb = RomBuilder::new();
+        rb.init_regs(0x0000, 0x000100, 0x000000, 0x000000);
+        rb.org(0x000100);
+        rb
+    }
+
+    /// Append a final `wait`, run one frame and return the memory
+    pub(crate) fn run(rb: &mut RomBuilder) -> Vec<u8> {
+        rb.wait();
+        let mut machine = Machine::from_builder(rb).unwrap();
+        machine.run_frame();
+        machine.memory().to_vec()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rom_builder::RomBuilder;
+
+    #[test]
+    fn test_machine_shows_screen_page() {
+        let mut rb = RomBuilder::new();
+        rb.init_regs(0x0000, 0x000100, 0x010000, 0x00FF00);
+        rb.org(0x000100).wait();
+        rb.org(0x00FF00).db_arr(&[7; 256]);
+        rb.org(0x010000).db_arr(&[42; 65536]);
+
+        let mut vm = Machine::from_builder(&rb).unwrap();
+        vm.run_frame();
+
+        assert_eq!(vm.program_counter(), 0x000100);
+        assert!(vm.screen().iter().all(|&x| x == 42));
+        assert!(vm.audio().iter().all(|&x| x == 7));
+    }
+
+    #[test]
+    fn test_machine_resumes_after_sync() {
+        let mut rb = RomBuilder::new();
+        rb.init_regs(0x0000, 0x000300, 0x010000, 0x00FF00);
+        rb.org(0x000100).install_id_table().install_inc_table();
+
+        let start = rb.new_label("start");
+        rb.org(0x000300).label(start);
+        rb.sync().inc(SCREEN_REGISTER_ADDR).jmp(start);
+
+        let mut vm = Machine::from_builder(&rb).unwrap();
+        vm.set_keys(0xA55A);
+        for frame in 1..=3 {
+            vm.run_frame();
+            assert_eq!(vm.frame_count(), frame);
+            assert_eq!(vm.screen_addr(), frame << 16);
+        }
+        assert_eq!(vm.memory()[0..2], [0xA5, 0x5A]);
+    }
+}
@@ -0,0 +1,103 @@
+//! Golden-frame regression tests for the shipped ROMs
+//!
+//! Each ROM is built through its library code, run headlessly and the
+//! hash of every displayed screen and audio page is compared against
+//! `tests/golden/<name>.txt`. Run with `UPDATE_GOLDEN=1` to regenerate
+//! the golden files after an intended change.
+
+use std::fmt::Write;
+use std::path::PathBuf;
+
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use rustedbytes_bytepusher_rombuilder::{
+    image::convert_image_dithered_strength, rom_builder::RomBuilder, roms,
+    video::process_png_sequence_flat, vm::Machine,
+};
+
+/// 64-bit FNV-1a hash
+fn fnv1a(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf29ce484222325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+fn frame_hashes(rom: &RomBuilder, frames: usize) -> String {
+    let mut machine = Machine::from_builder(rom).expect("ROM must be complete");
+
+    let mut report = String::from("# frame  screen            audio\n");
+    for frame in 0..frames {
+        machine.run_frame();
+        writeln!(
+            report,
+            "{:<8} {:016x}  {:016x}",
+            frame,
+            fnv1a(machine.screen()),
+            fnv1a(machine.audio())
+        )
+        .unwrap();
+    }
+    report
+}
+
+fn check_golden(name: &str, rom: &RomBuilder, frames: usize) {
+    let actual = frame_hashes(rom, frames);
+
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.push("tests/golden");
+    path.push(format!("{}.txt", name));
+
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::write(&path, &actual).expect("Failed to write golden file");
+        return;
+    }
+
+    let expected = std::fs::read_to_string(&path)
+        .unwrap_or_else(|_| panic!("Missing golden file {:?}, run with UPDATE_GOLDEN=1", path));
+    assert_eq!(
+        actual, expected,
+        "frames of '{}' differ from golden file",
+        name
+    );
+}
+
+#[test]
+fn golden_random_pattern() {
+    let rom = roms::random_pattern(&mut StdRng::seed_from_u64(2024)).unwrap();
+    check_golden("random", &rom, 2);
+}
+
+#[test]
+fn golden_image_evy() {
+    let image = convert_image_dithered_strength("resources/images/evy-256x256.png", 1.0)
+        .expect("Failed to load image file");
+    check_golden("evy", &roms::still_image(&image).unwrap(), 2);
+}
+
+#[test]
+fn golden_imageconv() {
+    let image = convert_image_dithered_strength("resources/images/lenna-256x256.png", 0.5)
+        .expect("Failed to load image file");
+    check_golden("imageconv", &roms::still_image(&image).unwrap(), 2);
+}
+
+#[test]
+fn golden_video_catwalk() {
+    // Only the first video frames, dithering the whole sequence is too slow for tests
+    let video = process_png_sequence_flat("resources/videos/frame_00000[1-3].png")
+        .expect("Failed to load video frames");
+    check_golden(
+        "catwalk",
+        &roms::video(&video, roms::VIDEO_HOLD_FRAMES).unwrap(),
+        16,
+    );
+}
+
+#[test]
+fn golden_animated_noise() {
+    check_golden(
+        "animated_noise",
+        &roms::animated_noise(roms::NOISE_HOLD_FRAMES).unwrap(),
+        20,
+    );
+}